@@ -0,0 +1,452 @@
+#![allow(dead_code)]
+
+use std::f32::consts::PI;
+use std::time;
+
+use sdl2::rect::{FPoint, FRect};
+use sdl2::render::RenderTarget;
+
+use crate::draw_primitives::draw_primitives::{draw_polygon_regular, draw_thick_line, fill_circle, fill_convex_polygon};
+use crate::theme::{key_value_lines, line_error, Theme};
+use crate::{Ball, BetterPoint, Collide, Collider, Drawable, Kinematic};
+
+const DEFAULT_WALL_THICKNESS: f32 = 8.0;
+
+fn dot(a: FPoint, b: FPoint) -> f32 {
+    a.x() * b.x() + a.y() * b.y()
+}
+
+fn cross(a: FPoint, b: FPoint) -> f32 {
+    a.x() * b.y() - a.y() * b.x()
+}
+
+// the point of the segment from `a` to `b` nearest to `point`
+fn closest_on_segment(point: FPoint, a: FPoint, b: FPoint) -> FPoint {
+    let edge = b - a;
+    let length_squared = dot(edge, edge);
+    if length_squared == 0.0 {
+        return a;
+    }
+    let t = (dot(point - a, edge) / length_squared).clamp(0.0, 1.0);
+    a + edge * t
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Shape {
+    Rect(FRect),
+    Circle { center: FPoint, radius: f32 },
+    // convex, the points go around the outline
+    Polygon(Vec<FPoint>),
+    // an angled wall, a line with round ends
+    Wall { from: FPoint, to: FPoint, thickness: f32 },
+}
+
+impl Shape {
+    fn translated(&self, offset: FPoint) -> Shape {
+        match self {
+            Shape::Rect(rect) => Shape::Rect(FRect::new(rect.x() + offset.x(), rect.y() + offset.y(), rect.width(), rect.height())),
+            Shape::Circle { center, radius } => Shape::Circle { center: *center + offset, radius: *radius },
+            Shape::Polygon(points) => Shape::Polygon(points.iter().map(|point| *point + offset).collect()),
+            Shape::Wall { from, to, thickness } => Shape::Wall { from: *from + offset, to: *to + offset, thickness: *thickness },
+        }
+    }
+
+    fn corners(rect: FRect) -> Vec<FPoint> {
+        vec![
+            FPoint::new(rect.left(), rect.top()),
+            FPoint::new(rect.right(), rect.top()),
+            FPoint::new(rect.right(), rect.bottom()),
+            FPoint::new(rect.left(), rect.bottom()),
+        ]
+    }
+
+    /// How a circle at `center` overlaps the shape: the direction to push it out and how far.
+    pub fn contact(&self, center: FPoint, radius: f32) -> Option<(FPoint, f32)> {
+        // pushes away from `surface`, `fallback` when the center sits right on it
+        let away = |surface: FPoint, reach: f32, fallback: FPoint| -> Option<(FPoint, f32)> {
+            let offset = center - surface;
+            let distance = offset.magnitude();
+            if distance >= reach {
+                return None;
+            }
+            let normal = if distance > 0.0 { offset / distance } else { fallback };
+            Some((normal, reach - distance))
+        };
+
+        match self {
+            Shape::Circle { center: circle_center, radius: circle_radius } => away(*circle_center, circle_radius + radius, FPoint::new(0.0, -1.0)),
+            Shape::Wall { from, to, thickness } => {
+                let edge = *to - *from;
+                let fallback = if edge.magnitude() > 0.0 { FPoint::new(-edge.y(), edge.x()).normalize() } else { FPoint::new(0.0, -1.0) };
+                away(closest_on_segment(center, *from, *to), thickness / 2.0 + radius, fallback)
+            }
+            Shape::Rect(rect) => Shape::Polygon(Shape::corners(*rect)).contact(center, radius),
+            Shape::Polygon(points) => {
+                let edges = || points.iter().zip(points.iter().cycle().skip(1));
+                let nearest = edges()
+                    .map(|(a, b)| closest_on_segment(center, *a, *b))
+                    .min_by(|a, b| (center - *a).magnitude().total_cmp(&(center - *b).magnitude()))?;
+
+                // inside a convex polygon the center is on the same side of every edge
+                let sides: Vec<f32> = edges().map(|(a, b)| cross(*b - *a, center - *a)).collect();
+                let inside = sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0);
+                if inside {
+                    let offset = nearest - center;
+                    let distance = offset.magnitude();
+                    let normal = if distance > 0.0 { offset / distance } else { FPoint::new(0.0, -1.0) };
+                    Some((normal, distance + radius))
+                } else {
+                    away(nearest, radius, FPoint::new(0.0, -1.0))
+                }
+            }
+        }
+    }
+}
+
+/// A static or moving shape the ball bounces off.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Obstacle {
+    pub shape: Shape,
+    // moves out by `travel` and back again every `period`, standing still without one
+    pub travel: FPoint,
+    pub period: Option<time::Duration>,
+}
+
+impl Obstacle {
+    pub fn fixed(shape: Shape) -> Obstacle {
+        Obstacle {
+            shape,
+            travel: FPoint::new(0.0, 0.0),
+            period: None,
+        }
+    }
+
+    /// The obstacle where it is `time` into the match, moving ones ease in and out at the ends.
+    pub fn at(&self, time: time::Duration) -> Obstacle {
+        let period = match self.period {
+            Some(period) if !period.is_zero() => period,
+            _ => return self.clone(),
+        };
+        let phase = (time.as_secs_f32() / period.as_secs_f32()).fract();
+        let progress = (1.0 - (phase * 2.0 * PI).cos()) / 2.0;
+        Obstacle {
+            shape: self.shape.translated(self.travel * progress),
+            travel: self.travel,
+            period: self.period,
+        }
+    }
+}
+
+impl Collider for Obstacle {
+    fn collider(&self) -> FRect {
+        match &self.shape {
+            Shape::Rect(rect) => *rect,
+            Shape::Circle { center, radius } => FRect::from_center(*center, radius * 2.0, radius * 2.0),
+            Shape::Polygon(points) => {
+                let left = points.iter().map(|point| point.x()).fold(f32::INFINITY, f32::min);
+                let right = points.iter().map(|point| point.x()).fold(f32::NEG_INFINITY, f32::max);
+                let top = points.iter().map(|point| point.y()).fold(f32::INFINITY, f32::min);
+                let bottom = points.iter().map(|point| point.y()).fold(f32::NEG_INFINITY, f32::max);
+                FRect::new(left, top, right - left, bottom - top)
+            }
+            Shape::Wall { from, to, thickness } => {
+                let left = from.x().min(to.x()) - thickness / 2.0;
+                let top = from.y().min(to.y()) - thickness / 2.0;
+                FRect::new(left, top, (from.x() - to.x()).abs() + thickness, (from.y() - to.y()).abs() + thickness)
+            }
+        }
+    }
+}
+
+// the bounding boxes weed out far away obstacles, the shape decides the actual bounce
+impl Collide<Obstacle> for Ball {
+    fn collide(&mut self, other: &Obstacle) {
+        if !self.collider().has_intersection(other.collider()) {
+            return;
+        }
+        let (normal, depth) = match other.shape.contact(self.pos, self.size as f32 / 2.0) {
+            Some(contact) => contact,
+            None => return,
+        };
+
+        self.translate(normal * depth);
+        // a ball already moving away was only caught up by a moving obstacle
+        let towards = dot(self.velocity, normal);
+        if towards < 0.0 {
+            self.velocity -= normal * (2.0 * towards);
+        }
+    }
+}
+
+impl Drawable for Obstacle {
+    fn draw<T: RenderTarget>(&self, canvas: &mut sdl2::render::Canvas<T>, theme: &Theme) -> Result<(), String> {
+        canvas.set_draw_color(theme.paddle);
+        match &self.shape {
+            Shape::Rect(rect) => fill_convex_polygon(canvas, &Shape::corners(*rect), theme.antialias),
+            Shape::Circle { center, radius } => fill_circle(canvas, *center, *radius, theme.antialias),
+            Shape::Polygon(points) => fill_convex_polygon(canvas, points, theme.antialias),
+            Shape::Wall { from, to, thickness } => {
+                draw_thick_line(canvas, *from, *to, *thickness, theme.antialias)?;
+                fill_circle(canvas, *from, thickness / 2.0, theme.antialias)?;
+                fill_circle(canvas, *to, thickness / 2.0, theme.antialias)
+            }
+        }
+    }
+}
+
+/// Two linked holes, a ball going into either one comes out of the other keeping its velocity.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Portal {
+    pub a: FPoint,
+    pub b: FPoint,
+    pub radius: f32,
+}
+
+impl Portal {
+    // true when the ball went through
+    pub fn teleport(&self, ball: &mut Ball) -> bool {
+        let exit = if (ball.pos - self.a).magnitude() < self.radius {
+            self.b
+        } else if (ball.pos - self.b).magnitude() < self.radius {
+            self.a
+        } else {
+            return false;
+        };
+
+        // out past the rim of the other hole, so it does not go straight back
+        let direction = if ball.velocity.magnitude() > 0.0 { ball.velocity.normalize() } else { FPoint::new(1.0, 0.0) };
+        ball.pos = exit + direction * (self.radius + ball.size as f32 / 2.0 + 1.0);
+        ball.trail.clear();
+        true
+    }
+}
+
+impl Drawable for Portal {
+    fn draw<T: RenderTarget>(&self, canvas: &mut sdl2::render::Canvas<T>, theme: &Theme) -> Result<(), String> {
+        canvas.set_draw_color(theme.ball);
+        for center in [self.a, self.b] {
+            for ring in [1.0, 0.7, 0.4] {
+                draw_polygon_regular(canvas, center, 24, self.radius * 2.0 * ring, 0.0)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Obstacles and portals placed on the play field, see `arenas/pinball.arena`.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Arena {
+    pub name: String,
+    pub obstacles: Vec<Obstacle>,
+    pub portals: Vec<Portal>,
+}
+
+impl Arena {
+    pub fn load(path: &std::path::Path) -> Result<Arena, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Arena::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parses an arena from `key = value` lines, comments work like in theme files. Every
+    /// `obstacle` adds a shape, optionally followed by `moves <dx> <dy> <seconds>`, and every
+    /// `portal` adds a pair of linked holes.
+    pub fn parse(text: &str) -> Result<Arena, String> {
+        let mut arena = Arena {
+            name: String::from("custom"),
+            ..Arena::default()
+        };
+
+        for line in key_value_lines(text) {
+            let (line, key, value) = line?;
+            let error = |message: String| line_error(line, message);
+
+            match key {
+                "name" => arena.name = value.to_string(),
+                "obstacle" => arena.obstacles.push(parse_obstacle(value).map_err(error)?),
+                "portal" => {
+                    let numbers = parse_numbers(value).map_err(error)?;
+                    if numbers.len() != 5 || numbers[4] <= 0.0 {
+                        return Err(error(format!("portal needs two points and a radius, got `{}`", value)));
+                    }
+                    arena.portals.push(Portal {
+                        a: FPoint::new(numbers[0], numbers[1]),
+                        b: FPoint::new(numbers[2], numbers[3]),
+                        radius: numbers[4],
+                    });
+                }
+                other => return Err(error(format!("unknown key `{}`", other))),
+            }
+        }
+        Ok(arena)
+    }
+
+    /// Bounces the ball off every obstacle as they are `time` into the match and sends it
+    /// through the first portal it is in. Returns the surface normal of each bounce.
+    pub fn collide(&self, ball: &mut Ball, time: time::Duration) -> Vec<FPoint> {
+        let mut bounces = Vec::new();
+        for obstacle in &self.obstacles {
+            let velocity_before = ball.velocity;
+            ball.collide(&obstacle.at(time));
+            if ball.velocity != velocity_before {
+                bounces.push((ball.velocity - velocity_before).normalize());
+            }
+        }
+        for portal in &self.portals {
+            if portal.teleport(ball) {
+                break;
+            }
+        }
+        bounces
+    }
+
+    pub fn draw<T: RenderTarget>(&self, canvas: &mut sdl2::render::Canvas<T>, theme: &Theme, time: time::Duration) -> Result<(), String> {
+        for portal in &self.portals {
+            portal.draw(canvas, theme)?;
+        }
+        for obstacle in &self.obstacles {
+            obstacle.at(time).draw(canvas, theme)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_numbers(value: &str) -> Result<Vec<f32>, String> {
+    value.split_whitespace().map(|number| number.parse::<f32>().map_err(|_| format!("invalid number `{}`", number))).collect()
+}
+
+fn parse_obstacle(value: &str) -> Result<Obstacle, String> {
+    let (shape, movement) = match value.split_once(" moves ") {
+        Some((shape, movement)) => (shape.trim(), Some(movement)),
+        None => (value, None),
+    };
+    let (kind, numbers) = shape.split_once(char::is_whitespace).ok_or_else(|| format!("obstacle `{}` has no size", value))?;
+    let numbers = parse_numbers(numbers)?;
+    let point = |index: usize| FPoint::new(numbers[index], numbers[index + 1]);
+
+    let shape = match kind {
+        "rect" if numbers.len() == 4 && numbers[2] > 0.0 && numbers[3] > 0.0 => Shape::Rect(FRect::new(numbers[0], numbers[1], numbers[2], numbers[3])),
+        "rect" => return Err(format!("rect needs x, y, width and height, got `{}`", shape)),
+        "circle" if numbers.len() == 3 && numbers[2] > 0.0 => Shape::Circle { center: point(0), radius: numbers[2] },
+        "circle" => return Err(format!("circle needs x, y and a radius, got `{}`", shape)),
+        "polygon" if numbers.len() >= 6 && numbers.len() % 2 == 0 => {
+            let points: Vec<FPoint> = (0..numbers.len()).step_by(2).map(point).collect();
+            if !is_convex(&points) {
+                return Err(format!("polygon `{}` is not convex", shape));
+            }
+            Shape::Polygon(points)
+        }
+        "polygon" => return Err(format!("polygon needs at least 3 points, got `{}`", shape)),
+        "wall" if numbers.len() == 4 || (numbers.len() == 5 && numbers[4] > 0.0) => Shape::Wall {
+            from: point(0),
+            to: point(2),
+            thickness: numbers.get(4).copied().unwrap_or(DEFAULT_WALL_THICKNESS),
+        },
+        "wall" => return Err(format!("wall needs two points and an optional thickness, got `{}`", shape)),
+        other => return Err(format!("unknown obstacle `{}`, expected rect, circle, polygon or wall", other)),
+    };
+
+    let mut obstacle = Obstacle::fixed(shape);
+    if let Some(movement) = movement {
+        let numbers = parse_numbers(movement)?;
+        if numbers.len() != 3 || numbers[2] <= 0.0 {
+            return Err(format!("moves needs dx, dy and the seconds for a round trip, got `{}`", movement));
+        }
+        obstacle.travel = FPoint::new(numbers[0], numbers[1]);
+        obstacle.period = Some(time::Duration::from_secs_f32(numbers[2]));
+    }
+    Ok(obstacle)
+}
+
+// every corner turns the same way and the outline does not go around more than once
+fn is_convex(points: &[FPoint]) -> bool {
+    let count = points.len();
+    let turns: Vec<f32> = (0..count).map(|i| cross(points[(i + 1) % count] - points[i], points[(i + 2) % count] - points[(i + 1) % count])).collect();
+    let same_way = turns.iter().all(|turn| *turn > 0.0) || turns.iter().all(|turn| *turn < 0.0);
+    let angle: f32 = (0..count).map(|i| {
+        let a = points[(i + 1) % count] - points[i];
+        let b = points[(i + 2) % count] - points[(i + 1) % count];
+        cross(a, b).atan2(dot(a, b))
+    }).sum();
+    same_way && (angle.abs() - 2.0 * PI).abs() < 0.01
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::arena::{Arena, Obstacle, Shape};
+    use crate::test_support::test_ball;
+    use crate::{Ball, Collide};
+    use sdl2::rect::{FPoint, FRect};
+    use std::time::Duration;
+
+    fn moving_ball(pos: FPoint, velocity: FPoint) -> Ball {
+        Ball { pos, velocity, ..test_ball() }
+    }
+
+    #[test]
+    fn parse_arena_test() {
+        let arena = Arena::parse("
+            name = test
+            obstacle = rect 100 100 50 20
+            obstacle = circle 400 300 40 # trailing comment
+            obstacle = polygon 300 450 340 520 260 520
+            obstacle = wall 200 80 300 160 12
+            obstacle = rect 390 200 20 60 moves 0 200 4
+            portal = 250 300 550 300 20
+        ").unwrap();
+
+        assert_eq!(arena.name, "test");
+        assert_eq!(arena.obstacles.len(), 5);
+        assert_eq!(arena.obstacles[0].shape, Shape::Rect(FRect::new(100.0, 100.0, 50.0, 20.0)));
+        assert_eq!(arena.obstacles[3].shape, Shape::Wall { from: FPoint::new(200.0, 80.0), to: FPoint::new(300.0, 160.0), thickness: 12.0 });
+        assert_eq!(arena.obstacles[4].period, Some(Duration::from_secs(4)));
+        assert_eq!(arena.portals.len(), 1);
+
+        // half way through the round trip the blocker is furthest out
+        assert_eq!(arena.obstacles[4].at(Duration::from_secs(2)).shape, Shape::Rect(FRect::new(390.0, 400.0, 20.0, 60.0)));
+        assert_eq!(arena.obstacles[4].at(Duration::from_secs(4)).shape, Shape::Rect(FRect::new(390.0, 200.0, 20.0, 60.0)));
+
+        assert!(Arena::load(std::path::Path::new("arenas/pinball.arena")).is_ok());
+    }
+
+    #[test]
+    fn parse_arena_errors_test() {
+        assert_eq!(Arena::parse("obstacle = star 1 2 3").unwrap_err(), "line 1: unknown obstacle `star`, expected rect, circle, polygon or wall");
+        assert!(Arena::parse("obstacle = circle 1 2").is_err());
+        assert!(Arena::parse("obstacle = polygon 0 0 100 0 0 100 100 100").is_err());
+        assert!(Arena::parse("obstacle = rect 0 0 10 10 moves 5 5").is_err());
+        assert!(Arena::parse("portal = 1 2 3 4").is_err());
+    }
+
+    #[test]
+    fn bounce_off_shapes_test() {
+        // head on into a circle comes straight back
+        let mut ball = moving_ball(FPoint::new(345.0, 300.0), FPoint::new(100.0, 0.0));
+        ball.collide(&Obstacle::fixed(Shape::Circle { center: FPoint::new(400.0, 300.0), radius: 50.0 }));
+        assert_eq!(ball.velocity, FPoint::new(-100.0, 0.0));
+        assert!(ball.pos.x() <= 340.0);
+
+        // a 45 degree wall turns a falling ball sideways
+        let mut ball = moving_ball(FPoint::new(400.0, 390.0), FPoint::new(0.0, 100.0));
+        ball.collide(&Obstacle::fixed(Shape::Wall { from: FPoint::new(300.0, 300.0), to: FPoint::new(500.0, 500.0), thickness: 4.0 }));
+        assert!(ball.velocity.x() > 99.0 && ball.velocity.y().abs() < 1.0);
+
+        // a ball that tunneled into a polygon is pushed out through the nearest edge
+        let mut ball = moving_ball(FPoint::new(105.0, 150.0), FPoint::new(100.0, 0.0));
+        ball.collide(&Obstacle::fixed(Shape::Polygon(vec![FPoint::new(100.0, 100.0), FPoint::new(200.0, 100.0), FPoint::new(200.0, 200.0), FPoint::new(100.0, 200.0)])));
+        assert!(ball.pos.x() <= 90.0);
+        assert_eq!(ball.velocity, FPoint::new(-100.0, 0.0));
+    }
+
+    #[test]
+    fn portal_test() {
+        let arena = Arena::parse("portal = 100 100 600 400 20").unwrap();
+        let mut ball = moving_ball(FPoint::new(105.0, 100.0), FPoint::new(0.0, 50.0));
+        arena.collide(&mut ball, Duration::ZERO);
+        assert_eq!(ball.pos, FPoint::new(600.0, 431.0));
+        assert_eq!(ball.velocity, FPoint::new(0.0, 50.0));
+
+        // coming out it is clear of both holes
+        arena.collide(&mut ball, Duration::ZERO);
+        assert_eq!(ball.pos, FPoint::new(600.0, 431.0));
+    }
+}
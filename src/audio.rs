@@ -0,0 +1,311 @@
+#![allow(dead_code)]
+
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::AudioSubsystem;
+
+use crate::music::{Sequencer, Song};
+use crate::GameEvent;
+
+pub const SAMPLE_RATE: i32 = 44100;
+// blips beyond this many at once steal the oldest voice
+const MAX_VOICES: usize = 8;
+// keeps a few overlapping blips from clipping
+const MASTER_GAIN: f32 = 0.25;
+
+/// Square wave sweeping linearly from `start_frequency` to `end_frequency` over `duration`
+/// seconds, with a linear fade out.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Blip {
+    pub start_frequency: f32,
+    pub end_frequency: f32,
+    pub duration: f32,
+    pub volume: f32,
+}
+
+impl Blip {
+    // speed_ratio is the ball speed relative to max_velocity, faster balls sound higher
+    pub fn paddle_hit(speed_ratio: f32) -> Blip {
+        let frequency = 440.0 * (1.0 + speed_ratio.clamp(0.0, 1.0));
+        Blip {
+            start_frequency: frequency,
+            end_frequency: frequency,
+            duration: 0.06,
+            volume: 1.0,
+        }
+    }
+
+    pub fn wall_bounce(speed_ratio: f32) -> Blip {
+        let frequency = 220.0 * (1.0 + speed_ratio.clamp(0.0, 1.0));
+        Blip {
+            start_frequency: frequency,
+            end_frequency: frequency,
+            duration: 0.04,
+            volume: 0.7,
+        }
+    }
+
+    pub fn goal() -> Blip {
+        Blip {
+            start_frequency: 660.0,
+            end_frequency: 110.0,
+            duration: 0.4,
+            volume: 1.0,
+        }
+    }
+
+    // lower and longer than a goal, nobody scored
+    pub fn ball_lost() -> Blip {
+        Blip {
+            start_frequency: 330.0,
+            end_frequency: 55.0,
+            duration: 0.6,
+            volume: 1.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Voice {
+    blip: Blip,
+    // position in samples
+    elapsed: u32,
+    length: u32,
+    // 0 to 1, position within the current square wave period
+    phase: f32,
+}
+
+/// Mixes the active blips and the music, this is the state the SDL audio thread calls into.
+/// Kept free of SDL so it can be driven directly.
+pub struct Synth {
+    sample_rate: f32,
+    voices: Vec<Voice>,
+    pub music: Option<Sequencer>,
+    // relative to the sound effects, both go through volume
+    pub music_volume: f32,
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Synth {
+    pub fn new(sample_rate: i32) -> Synth {
+        Synth {
+            sample_rate: sample_rate as f32,
+            voices: Vec::with_capacity(MAX_VOICES),
+            music: None,
+            music_volume: 0.6,
+            volume: 1.0,
+            muted: false,
+        }
+    }
+
+    pub fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    pub fn trigger(&mut self, blip: Blip) {
+        if self.voices.len() == MAX_VOICES {
+            self.voices.remove(0);
+        }
+        self.voices.push(Voice {
+            blip,
+            elapsed: 0,
+            length: (blip.duration * self.sample_rate) as u32,
+            phase: 0.0,
+        });
+    }
+
+    pub fn sample_rate(&self) -> i32 {
+        self.sample_rate as i32
+    }
+
+    /// Fills `out` with mono samples in the -1 to 1 range. Voices and music keep advancing
+    /// while muted.
+    pub fn render(&mut self, out: &mut [f32]) {
+        let gain = if self.muted { 0.0 } else { self.volume.clamp(0.0, 1.0) * MASTER_GAIN };
+
+        for sample in out.iter_mut() {
+            let mut mixed = match self.music.as_mut() {
+                Some(music) => music.next_sample() * self.music_volume.clamp(0.0, 1.0),
+                None => 0.0,
+            };
+            for voice in self.voices.iter_mut() {
+                if voice.elapsed >= voice.length {
+                    continue;
+                }
+                let t = voice.elapsed as f32 / voice.length as f32;
+                let frequency = voice.blip.start_frequency + (voice.blip.end_frequency - voice.blip.start_frequency) * t;
+                let square = if voice.phase < 0.5 { 1.0 } else { -1.0 };
+                mixed += square * voice.blip.volume * (1.0 - t);
+
+                voice.phase = (voice.phase + frequency / self.sample_rate).fract();
+                voice.elapsed += 1;
+            }
+            *sample = (mixed * gain).clamp(-1.0, 1.0);
+        }
+
+        self.voices.retain(|voice| voice.elapsed < voice.length);
+    }
+}
+
+impl AudioCallback for Synth {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.render(out);
+    }
+}
+
+/// Playback device running a `Synth`, everything goes through the device lock.
+pub struct AudioPlayer {
+    device: AudioDevice<Synth>,
+}
+
+impl AudioPlayer {
+    pub fn open(audio_subsystem: &AudioSubsystem) -> Result<AudioPlayer, String> {
+        let desired_spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: Some(512),
+        };
+        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| Synth::new(spec.freq))?;
+        device.resume();
+        Ok(AudioPlayer {
+            device,
+        })
+    }
+
+    pub fn play(&mut self, blip: Blip) {
+        self.device.lock().trigger(blip);
+    }
+
+    /// Plays the blip matching a game event, `max_velocity` scales the pitch.
+    pub fn play_event(&mut self, event: &GameEvent, max_velocity: f32) {
+        let speed_ratio = |speed: f32| if max_velocity > 0.0 { speed / max_velocity } else { 0.0 };
+        let blip = match *event {
+            GameEvent::PaddleHit { speed, .. } => Blip::paddle_hit(speed_ratio(speed)),
+            GameEvent::WallBounce { speed, .. } => Blip::wall_bounce(speed_ratio(speed)),
+            GameEvent::Goal { .. } => Blip::goal(),
+            GameEvent::BallLost { .. } => Blip::ball_lost(),
+        };
+        self.play(blip);
+    }
+
+    pub fn volume(&mut self) -> f32 {
+        self.device.lock().volume
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.device.lock().volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn is_muted(&mut self) -> bool {
+        self.device.lock().muted
+    }
+
+    pub fn toggle_mute(&mut self) {
+        let mut synth = self.device.lock();
+        synth.muted = !synth.muted;
+    }
+
+    /// Starts `song` from the beginning, replacing whatever was playing.
+    pub fn play_music(&mut self, song: Song) {
+        let mut synth = self.device.lock();
+        let sample_rate = synth.sample_rate();
+        synth.music = Some(Sequencer::new(song, sample_rate));
+    }
+
+    pub fn stop_music(&mut self) {
+        self.device.lock().music = None;
+    }
+
+    pub fn is_music_playing(&mut self) -> bool {
+        self.device.lock().music.is_some()
+    }
+
+    pub fn set_music_tempo(&mut self, tempo_scale: f32) {
+        if let Some(music) = self.device.lock().music.as_mut() {
+            music.tempo_scale = tempo_scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::{AudioPlayer, Blip, Synth, SAMPLE_RATE};
+    use crate::music::{Sequencer, Song};
+    use crate::{GameEvent, Side};
+    use sdl2::rect::FPoint;
+
+    #[test]
+    fn square_wave_test() {
+        let mut synth = Synth::new(SAMPLE_RATE);
+        synth.trigger(Blip::paddle_hit(0.0));
+
+        // 440 Hz is ~100 samples per period, the first half is high and the second low
+        let mut out = [0.0; 100];
+        synth.render(&mut out);
+        assert!(out[10] > 0.0);
+        assert!(out[60] < 0.0);
+
+        // the blip lasts 60ms and leaves silence behind
+        let mut rest = vec![0.0; SAMPLE_RATE as usize / 10];
+        synth.render(&mut rest);
+        assert_eq!(synth.active_voices(), 0);
+        assert_eq!(*rest.last().unwrap(), 0.0);
+    }
+
+    #[test]
+    fn volume_and_mute_test() {
+        let mut loud = Synth::new(SAMPLE_RATE);
+        let mut quiet = Synth::new(SAMPLE_RATE);
+        quiet.volume = 0.5;
+        loud.trigger(Blip::goal());
+        quiet.trigger(Blip::goal());
+
+        let (mut loud_out, mut quiet_out) = ([0.0; 16], [0.0; 16]);
+        loud.render(&mut loud_out);
+        quiet.render(&mut quiet_out);
+        assert!((quiet_out[0] * 2.0 - loud_out[0]).abs() < 1e-6);
+
+        loud.muted = true;
+        loud.render(&mut loud_out);
+        assert!(loud_out.iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn music_mix_test() {
+        let mut synth = Synth::new(SAMPLE_RATE);
+        let mut out = [0.0; 256];
+        synth.render(&mut out);
+        assert!(out.iter().all(|sample| *sample == 0.0));
+
+        synth.music = Some(Sequencer::new(Song::attract(), SAMPLE_RATE));
+        synth.render(&mut out);
+        assert!(out.iter().any(|sample| *sample != 0.0));
+    }
+
+    #[test]
+    fn pitch_follows_speed_test() {
+        assert!(Blip::paddle_hit(1.0).start_frequency > Blip::paddle_hit(0.2).start_frequency);
+        assert!(Blip::wall_bounce(1.0).start_frequency > Blip::wall_bounce(0.2).start_frequency);
+    }
+
+    #[test]
+    fn dummy_driver_test() {
+        sdl2::hint::set("SDL_AUDIODRIVER", "dummy");
+        let sdl_context = sdl2::init().unwrap();
+        let audio_subsystem = sdl_context.audio().unwrap();
+        let mut sound = AudioPlayer::open(&audio_subsystem).unwrap();
+
+        sound.set_volume(2.0);
+        assert_eq!(sound.volume(), 1.0);
+        sound.toggle_mute();
+        assert!(sound.is_muted());
+
+        sound.play_event(&GameEvent::Goal { scorer: Side::Left, pos: FPoint::new(0.0, 0.0) }, 1000.0);
+        sound.play_music(Song::attract());
+        sound.set_music_tempo(1.5);
+        assert!(sound.is_music_playing());
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+}
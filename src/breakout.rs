@@ -0,0 +1,573 @@
+#![allow(dead_code)]
+
+use std::f32::consts::PI;
+use std::time;
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::{FPoint, FRect};
+use sdl2::render::{BlendMode, RenderTarget};
+
+use crate::draw_primitives::draw_primitives::{draw_text, TextAlign};
+use crate::particles::{ParticleEmitter, ParticleSystem};
+use crate::theme::{key_value_lines, line_error, Theme};
+use crate::{Ball, BetterPoint, Collide, Collider, Drawable, GameController, GameEvent, GameRng, Kinematic, Paddle, PlayField, Side};
+
+pub const MAX_BRICK_HP: u32 = 9;
+const POINTS_PER_HIT: u32 = 10;
+// on top of the hits, for each hit point the brick had
+const POINTS_PER_BRICK: u32 = 50;
+// how far from straight up the ball leaves the ends of the paddle
+const MAX_BOUNCE_ANGLE: f32 = PI / 3.0;
+// and how far a launch may go either way
+const MAX_LAUNCH_ANGLE: f32 = PI / 8.0;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Brick {
+    pub rect: FRect,
+    pub hp: u32,
+    pub max_hp: u32,
+}
+
+impl Collider for Brick {
+    fn collider(&self) -> FRect {
+        self.rect
+    }
+}
+
+impl Collide<Brick> for Ball {
+    fn collide(&mut self, other: &Brick) {
+        self.bounce_off_rect(other.collider());
+    }
+}
+
+impl Brick {
+    // damaged bricks fade out
+    fn color(&self, theme: &Theme) -> Color {
+        let strength = 0.35 + 0.65 * self.hp as f32 / self.max_hp as f32;
+        Color::RGBA(theme.paddle.r, theme.paddle.g, theme.paddle.b, (theme.paddle.a as f32 * strength) as u8)
+    }
+}
+
+impl Drawable for Brick {
+    fn draw<T: RenderTarget>(&self, canvas: &mut sdl2::render::Canvas<T>, theme: &Theme) -> Result<(), String> {
+        let blend_mode = canvas.blend_mode();
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(self.color(theme));
+        let result = canvas.fill_frect(self.rect);
+        canvas.set_blend_mode(blend_mode);
+        result?;
+
+        // bricks that take more than one hit show how many are left
+        if self.max_hp > 1 {
+            canvas.set_draw_color(theme.hud);
+            canvas.draw_frect(self.rect)?;
+            let scale = (self.rect.height() / 10.0).floor().max(1.0);
+            draw_text(canvas, &self.hp.to_string(), FPoint::new(self.rect.center().x(), self.rect.center().y() - 3.5 * scale), scale, TextAlign::Center, theme.background)?;
+        }
+        Ok(())
+    }
+}
+
+/// A brick layout with the number of balls to start with, see `levels/classic.level`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Level {
+    pub name: String,
+    // balls at the start of a game, later levels keep what is left
+    pub lives: u32,
+    pub brick_size: FPoint,
+    pub brick_gap: f32,
+    // distance of the first row from the top of the field
+    pub top: f32,
+    // hit points of each brick from the top left, 0 leaves a gap
+    pub rows: Vec<Vec<u32>>,
+}
+
+impl Level {
+    pub fn builtin() -> Vec<Level> {
+        vec![
+            Level::parse(include_str!("../levels/classic.level")).expect("bundled level is valid"),
+            Level::parse(include_str!("../levels/fortress.level")).expect("bundled level is valid"),
+        ]
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Level, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Level::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parses a level from `key = value` lines, comments work like in theme files. Every `row`
+    /// adds a row of bricks below the previous ones, a digit is the hits that brick takes and
+    /// `.` leaves a gap.
+    pub fn parse(text: &str) -> Result<Level, String> {
+        let mut level = Level {
+            name: String::from("custom"),
+            lives: 3,
+            brick_size: FPoint::new(60.0, 20.0),
+            brick_gap: 4.0,
+            top: 80.0,
+            rows: Vec::new(),
+        };
+
+        for line in key_value_lines(text) {
+            let (line, key, value) = line?;
+            let error = |message: String| line_error(line, message);
+
+            match key {
+                "name" => level.name = value.to_string(),
+                "lives" => {
+                    level.lives = value.parse::<u32>().ok().filter(|lives| *lives > 0)
+                        .ok_or_else(|| error(format!("invalid lives `{}`", value)))?;
+                }
+                "brick_size" => {
+                    let size: Vec<f32> = value.split_whitespace().filter_map(|number| number.parse::<f32>().ok()).filter(|number| *number > 0.0).collect();
+                    if size.len() != 2 || value.split_whitespace().count() != 2 {
+                        return Err(error(format!("brick_size needs a width and a height, got `{}`", value)));
+                    }
+                    level.brick_size = FPoint::new(size[0], size[1]);
+                }
+                "brick_gap" => {
+                    level.brick_gap = value.parse::<f32>().ok().filter(|gap| *gap >= 0.0)
+                        .ok_or_else(|| error(format!("invalid brick_gap `{}`", value)))?;
+                }
+                "top" => level.top = value.parse::<f32>().map_err(|e| error(e.to_string()))?,
+                "row" => {
+                    let row = value.split_whitespace().map(parse_brick).collect::<Result<Vec<u32>, String>>().map_err(error)?;
+                    level.rows.push(row);
+                }
+                other => return Err(error(format!("unknown key `{}`", other))),
+            }
+        }
+
+        if level.rows.iter().flatten().all(|hp| *hp == 0) {
+            return Err(String::from("the level has no bricks"));
+        }
+        Ok(level)
+    }
+
+    /// The bricks laid out in `field`, the widest row is centered.
+    pub fn bricks(&self, field: FRect) -> Vec<Brick> {
+        let columns = self.rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let width = columns as f32 * (self.brick_size.x() + self.brick_gap) - self.brick_gap;
+        let left = field.center().x() - width / 2.0;
+
+        let mut bricks = Vec::new();
+        for (row_index, row) in self.rows.iter().enumerate() {
+            for (column, hp) in row.iter().enumerate().filter(|(_, hp)| **hp > 0) {
+                bricks.push(Brick {
+                    rect: FRect::new(
+                        left + column as f32 * (self.brick_size.x() + self.brick_gap),
+                        field.top() + self.top + row_index as f32 * (self.brick_size.y() + self.brick_gap),
+                        self.brick_size.x(),
+                        self.brick_size.y(),
+                    ),
+                    hp: *hp,
+                    max_hp: *hp,
+                });
+            }
+        }
+        bricks
+    }
+}
+
+fn parse_brick(token: &str) -> Result<u32, String> {
+    match token {
+        "." => Ok(0),
+        _ => token.parse::<u32>().ok().filter(|hp| (1..=MAX_BRICK_HP).contains(hp))
+            .ok_or_else(|| format!("invalid brick `{}`, expected 1 to {} or .", token, MAX_BRICK_HP)),
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BreakoutPhase {
+    // the ball rides on the paddle until it is launched
+    Serving,
+    Playing,
+    LevelCleared,
+    GameOver,
+}
+
+#[derive(Clone)]
+pub struct BreakoutState {
+    pub ball: Ball,
+    // lies along the bottom wall
+    pub paddle: Paddle,
+    pub bricks: Vec<Brick>,
+    pub canvas: FRect,
+    pub score: u32,
+    pub lives: u32,
+    // index into the controller's levels
+    pub level: usize,
+    pub level_name: String,
+    pub phase: BreakoutPhase,
+    pub rng: GameRng,
+}
+
+impl BreakoutState {
+    /// A new game on `level`, the paddle is turned to lie along the bottom wall if it stands upright.
+    pub fn new(ball: Ball, paddle: Paddle, canvas: FRect, level: &Level) -> BreakoutState {
+        let mut paddle = paddle;
+        if paddle.size.y() > paddle.size.x() {
+            paddle.size = FPoint::new(paddle.size.y(), paddle.size.x());
+        }
+        paddle.velocity = FPoint::new(0.0, 0.0);
+        paddle.pos = canvas.center();
+        paddle.pin_to_wall(Side::Bottom, canvas);
+
+        let mut state = BreakoutState {
+            ball,
+            paddle,
+            bricks: Vec::new(),
+            canvas,
+            score: 0,
+            lives: level.lives,
+            level: 0,
+            level_name: String::new(),
+            phase: BreakoutPhase::Serving,
+            rng: GameRng::new(rand::random()),
+        };
+        state.start_level(0, level);
+        state
+    }
+
+    pub fn start_level(&mut self, index: usize, level: &Level) {
+        self.level = index;
+        self.level_name = level.name.clone();
+        self.bricks = level.bricks(self.canvas);
+        self.phase = BreakoutPhase::Serving;
+        self.ball_on_paddle();
+    }
+
+    pub fn ball_on_paddle(&mut self) {
+        self.ball.pos = FPoint::new(self.paddle.pos.x(), self.paddle.pos.y() - self.paddle.size.y() / 2.0 - self.ball.size as f32 / 2.0 - 1.0);
+        self.ball.velocity = FPoint::new(0.0, 0.0);
+        self.ball.trail.clear();
+    }
+
+    // sends the ball up off the paddle at the serve velocity
+    pub fn launch(&mut self) {
+        let angle = -PI / 2.0 + (self.rng.next_f32() * 2.0 - 1.0) * MAX_LAUNCH_ANGLE;
+        self.ball.velocity = FPoint::new(
+            self.ball.physics.serve_velocity * angle.cos(),
+            self.ball.physics.serve_velocity * angle.sin(),
+        );
+        self.phase = BreakoutPhase::Playing;
+    }
+
+    pub fn lose_ball(&mut self) {
+        self.lives = self.lives.saturating_sub(1);
+        self.phase = if self.lives == 0 { BreakoutPhase::GameOver } else { BreakoutPhase::Serving };
+        self.ball_on_paddle();
+    }
+}
+
+/// Single player brick breaking on the pong engine. The paddle moves with A/D or the arrow
+/// keys, space launches the ball and moves on once a level is cleared or the game is over.
+pub struct BreakoutController {
+    // played in order and from the start again after the last one, never empty
+    pub levels: Vec<Level>,
+    pub theme: Theme,
+    // events accumulate until they are taken with `take_events`
+    pub events: Vec<GameEvent>,
+    // None disables particles entirely
+    pub particles: Option<ParticleSystem>,
+    left_pressed: bool,
+    right_pressed: bool,
+    launch_requested: bool,
+}
+
+impl BreakoutController {
+    pub fn new(levels: Vec<Level>) -> Result<BreakoutController, String> {
+        if levels.is_empty() {
+            return Err(String::from("breakout needs at least one level"));
+        }
+        Ok(BreakoutController {
+            levels,
+            theme: Theme::default_theme(),
+            events: Vec::new(),
+            particles: None,
+            left_pressed: false,
+            right_pressed: false,
+            launch_requested: false,
+        })
+    }
+
+    /// A new game on the first level.
+    pub fn new_game(&self, ball: Ball, paddle: Paddle, canvas: FRect) -> BreakoutState {
+        BreakoutState::new(ball, paddle, canvas, &self.levels[0])
+    }
+
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn read_input(&mut self, event: &Event) {
+        match event {
+            Event::KeyDown { keycode: Some(Keycode::A), .. } | Event::KeyDown { keycode: Some(Keycode::Left), .. } => self.left_pressed = true,
+            Event::KeyUp { keycode: Some(Keycode::A), .. } | Event::KeyUp { keycode: Some(Keycode::Left), .. } => self.left_pressed = false,
+            Event::KeyDown { keycode: Some(Keycode::D), .. } | Event::KeyDown { keycode: Some(Keycode::Right), .. } => self.right_pressed = true,
+            Event::KeyUp { keycode: Some(Keycode::D), .. } | Event::KeyUp { keycode: Some(Keycode::Right), .. } => self.right_pressed = false,
+            Event::KeyDown { keycode: Some(Keycode::Space), repeat: false, .. } => self.launch_requested = true,
+            _ => {}
+        }
+    }
+
+    fn play(&mut self, state: &mut BreakoutState, delta_t: time::Duration, play_field: &PlayField) {
+        let ball = &mut state.ball;
+        ball.update(delta_t);
+
+        // only a falling ball bounces off the paddle, where it lands decides where it goes,
+        // the restitution still speeds it up
+        if ball.velocity.y() > 0.0 && ball.collider().has_intersection(state.paddle.collider()) {
+            ball.collide(&state.paddle);
+            let offset = ((ball.pos.x() - state.paddle.pos.x()) / (state.paddle.length() / 2.0)).clamp(-1.0, 1.0);
+            let angle = -PI / 2.0 + offset * MAX_BOUNCE_ANGLE;
+            let speed = (ball.velocity.magnitude() * ball.physics.restitution_factor).clamp(0.0, ball.physics.max_velocity);
+            ball.velocity = FPoint::new(speed * angle.cos(), speed * angle.sin());
+            self.events.push(GameEvent::PaddleHit { side: Side::Bottom, pos: ball.pos, speed });
+        }
+
+        // one brick per tick, bouncing off two at once would send the ball straight back
+        if let Some(index) = state.bricks.iter().position(|brick| ball.collider().has_intersection(brick.collider())) {
+            let impact = ball.collider().intersection(state.bricks[index].collider()).unwrap_or(state.bricks[index].rect);
+            let velocity_before = ball.velocity;
+            ball.collide(&state.bricks[index]);
+            let normal = if ball.velocity.y().signum() != velocity_before.y().signum() {
+                FPoint::new(0.0, ball.velocity.y().signum())
+            } else {
+                FPoint::new(ball.velocity.x().signum(), 0.0)
+            };
+            self.events.push(GameEvent::WallBounce { pos: impact.center(), normal, speed: ball.velocity.magnitude() });
+
+            let brick = &mut state.bricks[index];
+            brick.hp -= 1;
+            state.score += POINTS_PER_HIT;
+            if brick.hp == 0 {
+                state.score += POINTS_PER_BRICK * brick.max_hp;
+                if let Some(particles) = self.particles.as_mut() {
+                    particles.burst(&ParticleEmitter::firework(brick.color(&self.theme)), brick.rect.center(), ball.velocity.angle());
+                }
+                state.bricks.remove(index);
+            }
+        }
+
+        // the bottom wall takes the ball
+        if play_field.touches(ball.collider(), Side::Bottom) {
+            self.events.push(GameEvent::BallLost { pos: ball.pos });
+            state.lose_ball();
+            return;
+        }
+
+        let velocity_before_walls = ball.velocity;
+        ball.collide(play_field);
+        if ball.velocity != velocity_before_walls {
+            let normal = if ball.velocity.y().signum() != velocity_before_walls.y().signum() {
+                FPoint::new(0.0, 1.0)
+            } else {
+                FPoint::new(ball.velocity.x().signum(), 0.0)
+            };
+            self.events.push(GameEvent::WallBounce { pos: ball.pos, normal, speed: ball.velocity.magnitude() });
+        }
+
+        if state.bricks.is_empty() {
+            state.phase = BreakoutPhase::LevelCleared;
+        }
+    }
+
+    fn spawn_particles(&mut self, events_from: usize) {
+        let particles = match self.particles.as_mut() {
+            Some(particles) => particles,
+            None => return,
+        };
+
+        for event in &self.events[events_from..] {
+            match *event {
+                GameEvent::PaddleHit { side, pos, .. } => {
+                    particles.burst(&ParticleEmitter::sparks(self.theme.ball), pos, side.inward_angle());
+                }
+                GameEvent::WallBounce { pos, normal, .. } => {
+                    particles.burst(&ParticleEmitter::dust(self.theme.ball), pos, normal.angle());
+                }
+                GameEvent::Goal { .. } | GameEvent::BallLost { .. } => {}
+            }
+        }
+    }
+
+    fn draw_hud<T: RenderTarget>(&self, state: &BreakoutState, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String> {
+        let color = self.theme.hud;
+        let field = state.canvas;
+        let unit = field.height() / 600.0;
+        let hud_y = field.top() + 20.0 * unit;
+
+        draw_text(canvas, &format!("SCORE {}", state.score), FPoint::new(field.left() + 20.0 * unit, hud_y), 2.0 * unit, TextAlign::Left, color)?;
+        draw_text(canvas, &state.level_name.to_uppercase(), FPoint::new(field.center().x(), hud_y), 2.0 * unit, TextAlign::Center, color)?;
+        draw_text(canvas, &format!("BALLS {}", state.lives), FPoint::new(field.right() - 20.0 * unit, hud_y), 2.0 * unit, TextAlign::Right, color)?;
+
+        let (title, hint) = match state.phase {
+            BreakoutPhase::Serving => ("", "SPACE TO LAUNCH"),
+            BreakoutPhase::Playing => return Ok(()),
+            BreakoutPhase::LevelCleared => ("LEVEL CLEAR", "SPACE FOR THE NEXT LEVEL"),
+            BreakoutPhase::GameOver => ("GAME OVER", "SPACE TO PLAY AGAIN"),
+        };
+        let message_y = field.bottom() - 200.0 * unit;
+        draw_text(canvas, title, FPoint::new(field.center().x(), message_y), 5.0 * unit, TextAlign::Center, color)?;
+        draw_text(canvas, hint, FPoint::new(field.center().x(), message_y + 50.0 * unit), 2.0 * unit, TextAlign::Center, color)?;
+        Ok(())
+    }
+}
+
+impl GameController<BreakoutState> for BreakoutController {
+    fn update(&mut self, game_state: &BreakoutState, _next_tick: time::Instant, delta_t: time::Duration, event: Event) -> Result<BreakoutState, String> {
+        let mut state = game_state.clone();
+        let play_field = PlayField::from_rect(state.canvas);
+        self.read_input(&event);
+
+        let paddle = &mut state.paddle;
+        paddle.pin_to_wall(Side::Bottom, play_field.rect);
+        paddle.velocity = match (self.left_pressed, self.right_pressed) {
+            (true, false) => paddle.axis() * -paddle.movement_speed,
+            (false, true) => paddle.axis() * paddle.movement_speed,
+            _ => FPoint::new(0.0, 0.0),
+        };
+        paddle.update(delta_t);
+        paddle.collide(&play_field);
+
+        let events_from = self.events.len();
+        let launch = std::mem::take(&mut self.launch_requested);
+        match state.phase {
+            BreakoutPhase::Serving => {
+                state.ball_on_paddle();
+                if launch {
+                    state.launch();
+                }
+            }
+            BreakoutPhase::Playing => self.play(&mut state, delta_t, &play_field),
+            BreakoutPhase::LevelCleared if launch => {
+                let next = (state.level + 1) % self.levels.len();
+                state.start_level(next, &self.levels[next]);
+            }
+            BreakoutPhase::GameOver if launch => {
+                state = self.new_game(state.ball, state.paddle, state.canvas);
+            }
+            BreakoutPhase::LevelCleared | BreakoutPhase::GameOver => {}
+        }
+
+        self.spawn_particles(events_from);
+        if let Some(particles) = self.particles.as_mut() {
+            particles.update(delta_t);
+        }
+        Ok(state)
+    }
+
+    fn draw<T: RenderTarget>(&self, game_state: &BreakoutState, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String> {
+        self.draw_hud(game_state, canvas)?;
+        for brick in &game_state.bricks {
+            brick.draw(canvas, &self.theme)?;
+        }
+        if let Some(particles) = &self.particles {
+            particles.draw(canvas)?;
+        }
+        if game_state.phase != BreakoutPhase::GameOver {
+            game_state.ball.draw(canvas, &self.theme)?;
+        }
+        game_state.paddle.draw(canvas, &self.theme)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::breakout::{BreakoutController, BreakoutPhase, BreakoutState, Level};
+    use crate::test_support::{key, no_event, test_ball, test_paddle};
+    use crate::{Ball, GameController, GameEvent};
+    use sdl2::keyboard::Keycode;
+    use sdl2::rect::{FPoint, FRect};
+    use std::time;
+
+    fn test_game(level: &str) -> (BreakoutController, BreakoutState) {
+        let ball = Ball { size: 10, ..test_ball() };
+        let paddle = test_paddle();
+        let controller = BreakoutController::new(vec![Level::parse(level).unwrap()]).unwrap();
+        let state = controller.new_game(ball, paddle, FRect::new(0.0, 0.0, 800.0, 600.0));
+        (controller, state)
+    }
+
+    #[test]
+    fn parse_level_test() {
+        let level = Level::parse("
+            name = steps # trailing comment
+            lives = 2
+            brick_size = 40 10
+            brick_gap = 0
+            top = 50
+            row = 1 . 3
+            row = 9
+        ").unwrap();
+
+        assert_eq!(level.name, "steps");
+        assert_eq!(level.lives, 2);
+        assert_eq!(level.rows, vec![vec![1, 0, 3], vec![9]]);
+
+        let bricks = level.bricks(FRect::new(0.0, 0.0, 800.0, 600.0));
+        assert_eq!(bricks.len(), 3);
+        assert_eq!(bricks[0].rect, FRect::new(340.0, 50.0, 40.0, 10.0));
+        assert_eq!(bricks[1].rect, FRect::new(420.0, 50.0, 40.0, 10.0));
+        assert_eq!(bricks[1].hp, 3);
+        assert_eq!(bricks[2].rect, FRect::new(340.0, 60.0, 40.0, 10.0));
+
+        assert_eq!(Level::builtin().len(), 2);
+    }
+
+    #[test]
+    fn parse_level_errors_test() {
+        assert_eq!(Level::parse("row = 1 0 1").unwrap_err(), "line 1: invalid brick `0`, expected 1 to 9 or .");
+        assert_eq!(Level::parse("name = empty\nrow = . .").unwrap_err(), "the level has no bricks");
+        assert!(Level::parse("row = 1\nbrick_size = 40").is_err());
+        assert!(Level::parse("row = 1\nlives = 0").is_err());
+        assert!(Level::parse("row = 1\nballs = 3").is_err());
+    }
+
+    #[test]
+    fn brick_takes_hits_until_destroyed_test() {
+        let (mut controller, mut state) = test_game("row = 2");
+        assert_eq!(state.phase, BreakoutPhase::Serving);
+        assert_eq!(state.paddle.size, FPoint::new(100.0, 20.0));
+
+        // the ball hits the brick from below and comes back down
+        let brick = state.bricks[0].rect;
+        state.phase = BreakoutPhase::Playing;
+        state.ball.pos = FPoint::new(brick.center().x(), brick.bottom() + 6.0);
+        state.ball.velocity = FPoint::new(0.0, -200.0);
+        let state = controller.update(&state, time::Instant::now(), time::Duration::from_millis(10), no_event()).unwrap();
+        assert_eq!(state.bricks[0].hp, 1);
+        assert_eq!(state.score, 10);
+        assert!(state.ball.velocity.y() > 0.0);
+
+        let mut state = state;
+        state.ball.pos = FPoint::new(brick.center().x(), brick.bottom() + 6.0);
+        state.ball.velocity = FPoint::new(0.0, -200.0);
+        let state = controller.update(&state, time::Instant::now(), time::Duration::from_millis(10), no_event()).unwrap();
+        assert!(state.bricks.is_empty());
+        assert_eq!(state.score, 10 + 10 + 2 * 50);
+        assert_eq!(state.phase, BreakoutPhase::LevelCleared);
+    }
+
+    #[test]
+    fn losing_the_last_ball_ends_the_game_test() {
+        let (mut controller, mut state) = test_game("lives = 1\nrow = 1");
+        state.phase = BreakoutPhase::Playing;
+        state.ball.pos = FPoint::new(100.0, 596.0);
+        state.ball.velocity = FPoint::new(0.0, 200.0);
+
+        let state = controller.update(&state, time::Instant::now(), time::Duration::from_millis(10), no_event()).unwrap();
+        assert_eq!(state.lives, 0);
+        assert_eq!(state.phase, BreakoutPhase::GameOver);
+        assert!(matches!(controller.take_events()[..], [GameEvent::BallLost { .. }]));
+
+        // space starts over
+        let state = controller.update(&state, time::Instant::now(), time::Duration::ZERO, key(Keycode::Space)).unwrap();
+        assert_eq!(state.lives, 1);
+        assert_eq!(state.score, 0);
+        assert_eq!(state.phase, BreakoutPhase::Serving);
+    }
+}
@@ -10,7 +10,10 @@ pub mod draw_primitives {
     use sdl2::video::Window;
     use sdl2::render::RenderTarget;
     use sdl2::rect::{FPoint, Point};
-
+    use sdl2::rect::FRect;
+    use sdl2::pixels::Color;
+    use sdl2::render::BlendMode;
+    use sdl2::sys::SDL_Vertex;
 
     pub fn draw_polygon_regular<T: RenderTarget>(canvas: &mut sdl2::render::Canvas<T>, center: FPoint, edges: u32, size: f32, angle_offset: f32 ) -> Result<(), String> {
         let mut points: Vec<FPoint> = Vec::new();
@@ -27,34 +30,472 @@ pub mod draw_primitives {
         Ok(())
     }
     
-    pub fn draw_gradient_rect<T: RenderTarget>(canvas: &mut sdl2::render::Canvas<T>, gradient_base_texture: &mut Texture, dest_rect: Rect, angle: f64, color1: sdl2::pixels::Color, color2: sdl2::pixels::Color) -> Result<(), String> {
-        gradient_base_texture.set_blend_mode(sdl2::render::BlendMode::Blend);
-        gradient_base_texture.set_alpha_mod(255);
-        
-        gradient_base_texture.set_color_mod(color1.r, color1.g, color1.b);
-
-        canvas.set_clip_rect(dest_rect);
-        canvas.copy_ex(
-            &gradient_base_texture, 
-            None, 
-            None,
-            angle, 
-            None, 
-            false, 
-            false)?;
-
-        gradient_base_texture.set_color_mod(color2.r, color2.g, color2.b);
-
-        canvas.copy_ex(
-            &gradient_base_texture, 
-            None, 
-            None, 
-            angle, 
-            None, 
-            true, 
-            true)?;
-
-        canvas.set_clip_rect(None);
+    // width of the transparent fringe added around shapes drawn with antialiasing
+    const ANTIALIAS_FEATHER: f32 = 1.0;
+
+    pub fn vertex(position: FPoint, color: Color, tex_coord: FPoint) -> SDL_Vertex {
+        SDL_Vertex {
+            position: sdl2::sys::SDL_FPoint { x: position.x(), y: position.y() },
+            color: sdl2::sys::SDL_Color { r: color.r, g: color.g, b: color.b, a: color.a },
+            tex_coord: sdl2::sys::SDL_FPoint { x: tex_coord.x(), y: tex_coord.y() },
+        }
+    }
+
+    /// Safe wrapper around `SDL_RenderGeometry`, the sdl2 crate does not expose it yet.
+    /// `indices` may be empty, in which case every three vertices form a triangle.
+    pub fn render_geometry<T: RenderTarget>(canvas: &mut sdl2::render::Canvas<T>, texture: Option<&Texture>, vertices: &[SDL_Vertex], indices: &[i32]) -> Result<(), String> {
+        let texture_raw = texture.map_or(std::ptr::null_mut(), |texture| texture.raw());
+        let indices_ptr = if indices.is_empty() { std::ptr::null() } else { indices.as_ptr() };
+
+        let result = unsafe {
+            sdl2::sys::SDL_RenderGeometry(
+                canvas.raw(),
+                texture_raw,
+                vertices.as_ptr(),
+                vertices.len() as i32,
+                indices_ptr,
+                indices.len() as i32,
+            )
+        };
+
+        if result != 0 {
+            return Err(sdl2::get_error());
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn regular_polygon_points(center: FPoint, edges: u32, size: f32, angle_offset: f32) -> Vec<FPoint> {
+        let r = size / 2.0;
+        (0..edges).map(|i| {
+            let fi = (std::f32::consts::PI * 2.0 / edges as f32 * i as f32) + angle_offset;
+            FPoint::new(center.x + (r * fi.cos()), center.y + (r * fi.sin()))
+        }).collect()
+    }
+
+    fn rounded_rect_points(rect: FRect, radius: f32) -> Vec<FPoint> {
+        let radius = radius.clamp(0.0, rect.width().min(rect.height()) / 2.0);
+        let corner_segments = ((radius * std::f32::consts::PI / 2.0) / 4.0).ceil().max(1.0) as u32;
+
+        // corner centers in clockwise order starting at the top left, with the angle each arc starts at
+        let corners = [
+            (FPoint::new(rect.left() + radius, rect.top() + radius), std::f32::consts::PI),
+            (FPoint::new(rect.right() - radius, rect.top() + radius), std::f32::consts::PI * 1.5),
+            (FPoint::new(rect.right() - radius, rect.bottom() - radius), 0.0),
+            (FPoint::new(rect.left() + radius, rect.bottom() - radius), std::f32::consts::PI * 0.5),
+        ];
+
+        let mut points: Vec<FPoint> = Vec::new();
+        for (center, start_angle) in corners {
+            for i in 0..=corner_segments {
+                let angle = start_angle + std::f32::consts::PI / 2.0 * i as f32 / corner_segments as f32;
+                points.push(FPoint::new(center.x() + radius * angle.cos(), center.y() + radius * angle.sin()));
+            }
+        }
+        points
+    }
+
+    /// Fills a convex polygon with the current draw color. With `antialias` the edges fade out
+    /// over a one pixel wide fringe instead of being cut hard.
+    pub fn fill_convex_polygon<T: RenderTarget>(canvas: &mut sdl2::render::Canvas<T>, points: &[FPoint], antialias: bool) -> Result<(), String> {
+        if points.len() < 3 {
+            return Ok(());
+        }
+
+        let color = canvas.draw_color();
+        let no_tex = FPoint::new(0.0, 0.0);
+        let count = points.len();
+
+        let mut vertices: Vec<SDL_Vertex> = points.iter().map(|point| vertex(*point, color, no_tex)).collect();
+        let mut indices: Vec<i32> = Vec::new();
+        for i in 1..count - 1 {
+            indices.extend_from_slice(&[0, i as i32, i as i32 + 1]);
+        }
+
+        if antialias {
+            let centroid_sum = points.iter().fold(FPoint::new(0.0, 0.0), |sum, point| sum + *point);
+            let centroid = FPoint::new(centroid_sum.x() / count as f32, centroid_sum.y() / count as f32);
+
+            let outward_normal = |a: FPoint, b: FPoint| -> FPoint {
+                let edge = b - a;
+                let length = edge.x().hypot(edge.y()).max(f32::EPSILON);
+                let normal = FPoint::new(edge.y() / length, -edge.x() / length);
+                let midpoint = FPoint::new((a.x() + b.x()) / 2.0, (a.y() + b.y()) / 2.0);
+                let to_edge = midpoint - centroid;
+                if normal.x() * to_edge.x() + normal.y() * to_edge.y() < 0.0 {
+                    FPoint::new(-normal.x(), -normal.y())
+                } else {
+                    normal
+                }
+            };
+
+            let transparent = Color::RGBA(color.r, color.g, color.b, 0);
+            for i in 0..count {
+                let previous = points[(i + count - 1) % count];
+                let next = points[(i + 1) % count];
+                let n1 = outward_normal(previous, points[i]);
+                let n2 = outward_normal(points[i], next);
+                // miter offset keeps the fringe the same width along both edges
+                let miter = (1.0 + n1.x() * n2.x() + n1.y() * n2.y()).max(0.1);
+                let offset = FPoint::new((n1.x() + n2.x()) / miter, (n1.y() + n2.y()) / miter) * ANTIALIAS_FEATHER;
+                vertices.push(vertex(points[i] + offset, transparent, no_tex));
+            }
+
+            for i in 0..count {
+                let inner_a = i as i32;
+                let inner_b = ((i + 1) % count) as i32;
+                let outer_a = (count + i) as i32;
+                let outer_b = (count + (i + 1) % count) as i32;
+                indices.extend_from_slice(&[inner_a, outer_a, outer_b, inner_a, outer_b, inner_b]);
+            }
+        }
+
+        let blend_mode = canvas.blend_mode();
+        if antialias || color.a < 255 {
+            canvas.set_blend_mode(BlendMode::Blend);
+        }
+        let result = render_geometry(canvas, None, vertices.as_slice(), indices.as_slice());
+        canvas.set_blend_mode(blend_mode);
+        result
+    }
+
+    pub fn fill_polygon_regular<T: RenderTarget>(canvas: &mut sdl2::render::Canvas<T>, center: FPoint, edges: u32, size: f32, angle_offset: f32, antialias: bool) -> Result<(), String> {
+        fill_convex_polygon(canvas, regular_polygon_points(center, edges, size, angle_offset).as_slice(), antialias)
+    }
+
+    pub fn fill_circle<T: RenderTarget>(canvas: &mut sdl2::render::Canvas<T>, center: FPoint, radius: f32, antialias: bool) -> Result<(), String> {
+        // roughly one edge every 4 pixels of circumference
+        let edges = ((radius * std::f32::consts::PI * 2.0) / 4.0).ceil().clamp(12.0, 256.0) as u32;
+        fill_polygon_regular(canvas, center, edges, radius * 2.0, 0.0, antialias)
+    }
+
+    pub fn draw_thick_line<T: RenderTarget>(canvas: &mut sdl2::render::Canvas<T>, start: FPoint, end: FPoint, thickness: f32, antialias: bool) -> Result<(), String> {
+        let direction = end - start;
+        let length = direction.x().hypot(direction.y());
+        if length == 0.0 {
+            return Ok(());
+        }
+
+        let half_width = FPoint::new(-direction.y() / length, direction.x() / length) * (thickness / 2.0);
+        let quad = [start + half_width, end + half_width, end - half_width, start - half_width];
+        fill_convex_polygon(canvas, &quad, antialias)
+    }
+
+    pub fn draw_rounded_rect<T: RenderTarget>(canvas: &mut sdl2::render::Canvas<T>, rect: FRect, radius: f32) -> Result<(), String> {
+        let mut points = rounded_rect_points(rect, radius);
+        points.push(points[0]);
+        canvas.draw_flines(points.as_slice())?;
+        Ok(())
+    }
+
+    pub fn fill_rounded_rect<T: RenderTarget>(canvas: &mut sdl2::render::Canvas<T>, rect: FRect, radius: f32, antialias: bool) -> Result<(), String> {
+        fill_convex_polygon(canvas, rounded_rect_points(rect, radius).as_slice(), antialias)
+    }
+
+    /// A small bitmap drawn pixel by pixel as colored quads, so it can be rotated and scaled
+    /// without a texture. Pixels with zero alpha are skipped.
+    #[derive(Clone, PartialEq, Debug)]
+    pub struct Sprite {
+        pub width: u32,
+        pub height: u32,
+        pub pixels: Vec<Color>,
+    }
+
+    impl Sprite {
+        /// Builds a sprite from rows of characters, each character is looked up in `palette`
+        /// and anything not in it is transparent.
+        pub fn from_art(rows: &[&str], palette: &[(char, Color)]) -> Sprite {
+            let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0) as u32;
+            let mut pixels: Vec<Color> = Vec::new();
+            for row in rows {
+                let mut chars = row.chars();
+                for _ in 0..width {
+                    let color = chars.next()
+                        .and_then(|c| palette.iter().find(|(key, _)| *key == c))
+                        .map_or(Color::RGBA(0, 0, 0, 0), |(_, color)| *color);
+                    pixels.push(color);
+                }
+            }
+            Sprite {
+                width,
+                height: rows.len() as u32,
+                pixels,
+            }
+        }
+
+        /// Loads a BMP file, pure magenta (#FF00FF) is treated as transparent.
+        pub fn load_bmp(path: &std::path::Path) -> Result<Sprite, String> {
+            let surface = sdl2::surface::Surface::load_bmp(path)?
+                .convert_format(sdl2::pixels::PixelFormatEnum::RGBA32)?;
+            let width = surface.width();
+            let height = surface.height();
+            let pitch = surface.pitch() as usize;
+
+            let pixels = surface.with_lock(|buffer: &[u8]| {
+                let mut pixels: Vec<Color> = Vec::with_capacity((width * height) as usize);
+                for y in 0..height as usize {
+                    for x in 0..width as usize {
+                        let i = y * pitch + x * 4;
+                        let (r, g, b, a) = (buffer[i], buffer[i + 1], buffer[i + 2], buffer[i + 3]);
+                        if (r, g, b) == (255, 0, 255) {
+                            pixels.push(Color::RGBA(0, 0, 0, 0));
+                        } else {
+                            pixels.push(Color::RGBA(r, g, b, a));
+                        }
+                    }
+                }
+                pixels
+            });
+
+            Ok(Sprite {
+                width,
+                height,
+                pixels,
+            })
+        }
+    }
+
+    /// Draws `sprite` centered on `center`, scaled so its larger side is `size` and rotated by `angle` radians.
+    pub fn draw_sprite<T: RenderTarget>(canvas: &mut sdl2::render::Canvas<T>, sprite: &Sprite, center: FPoint, size: f32, angle: f32) -> Result<(), String> {
+        if sprite.width == 0 || sprite.height == 0 {
+            return Ok(());
+        }
+
+        let pixel_size = size / sprite.width.max(sprite.height) as f32;
+        let (sin, cos) = angle.sin_cos();
+        let rotate = |x: f32, y: f32| FPoint::new(center.x() + x * cos - y * sin, center.y() + x * sin + y * cos);
+        let no_tex = FPoint::new(0.0, 0.0);
+
+        let mut vertices: Vec<SDL_Vertex> = Vec::new();
+        let mut indices: Vec<i32> = Vec::new();
+        for y in 0..sprite.height {
+            for x in 0..sprite.width {
+                let color = sprite.pixels[(y * sprite.width + x) as usize];
+                if color.a == 0 {
+                    continue;
+                }
+
+                let left = (x as f32 - sprite.width as f32 / 2.0) * pixel_size;
+                let top = (y as f32 - sprite.height as f32 / 2.0) * pixel_size;
+                let first = vertices.len() as i32;
+                vertices.push(vertex(rotate(left, top), color, no_tex));
+                vertices.push(vertex(rotate(left + pixel_size, top), color, no_tex));
+                vertices.push(vertex(rotate(left + pixel_size, top + pixel_size), color, no_tex));
+                vertices.push(vertex(rotate(left, top + pixel_size), color, no_tex));
+                indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+            }
+        }
+
+        let blend_mode = canvas.blend_mode();
+        canvas.set_blend_mode(BlendMode::Blend);
+        let result = render_geometry(canvas, None, vertices.as_slice(), indices.as_slice());
+        canvas.set_blend_mode(blend_mode);
+        result
+    }
+
+    pub const GLYPH_WIDTH: u32 = 5;
+    pub const GLYPH_HEIGHT: u32 = 7;
+    // one column of spacing between glyphs and one row between lines, in font pixels
+    const GLYPH_ADVANCE: u32 = GLYPH_WIDTH + 1;
+    const LINE_ADVANCE: u32 = GLYPH_HEIGHT + 1;
+
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    pub enum TextAlign {
+        Left,
+        Center,
+        Right,
+    }
+
+    // 5x7 bitmap font, one byte per row, bit 4 is the leftmost column
+    fn glyph(c: char) -> [u8; 7] {
+        match c.to_ascii_uppercase() {
+            ' ' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+            '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+            '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+            '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+            '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+            '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+            '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+            '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+            '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+            '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+            'A' => [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11],
+            'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+            'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+            'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+            'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+            'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+            'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+            'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+            'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+            'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+            'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+            'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+            'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+            'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+            'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+            'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+            'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+            'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+            'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+            'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+            'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+            'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+            'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+            'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+            'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+            'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+            '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+            ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+            ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+            ';' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08],
+            '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+            '?' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+            '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+            '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+            '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+            '*' => [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00],
+            '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+            '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+            '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+            '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+            ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+            '[' => [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E],
+            ']' => [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E],
+            '<' => [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02],
+            '>' => [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08],
+            '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+            '\'' => [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+            '"' => [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00],
+            // unknown characters are drawn as a hollow box
+            _ => [0x1F, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1F],
+        }
+    }
+
+    pub fn text_size(text: &str, scale: f32) -> (f32, f32) {
+        let columns = text.lines().map(|line| line.chars().count()).max().unwrap_or(0) as u32;
+        let rows = text.lines().count().max(1) as u32;
+
+        let width = (columns * GLYPH_ADVANCE).saturating_sub(1);
+        let height = rows * LINE_ADVANCE - 1;
+        (width as f32 * scale, height as f32 * scale)
+    }
+
+    /// Draws `text` with the built-in 5x7 font. `pos` is the top of the text block, its x is
+    /// interpreted as the left edge, center or right edge depending on `align`.
+    pub fn draw_text<T: RenderTarget>(canvas: &mut sdl2::render::Canvas<T>, text: &str, pos: FPoint, scale: f32, align: TextAlign, color: Color) -> Result<(), String> {
+        let mut rects: Vec<FRect> = Vec::new();
+
+        for (line_index, line) in text.lines().enumerate() {
+            let (line_width, _) = text_size(line, scale);
+            let line_x = match align {
+                TextAlign::Left => pos.x(),
+                TextAlign::Center => pos.x() - line_width / 2.0,
+                TextAlign::Right => pos.x() - line_width,
+            };
+            let line_y = pos.y() + (line_index as u32 * LINE_ADVANCE) as f32 * scale;
+
+            for (char_index, c) in line.chars().enumerate() {
+                let glyph_x = line_x + (char_index as u32 * GLYPH_ADVANCE) as f32 * scale;
+
+                for (row_index, row) in glyph(c).iter().enumerate() {
+                    let y = line_y + row_index as f32 * scale;
+
+                    // merge horizontal runs of lit pixels into a single rect
+                    let mut column = 0;
+                    while column < GLYPH_WIDTH {
+                        if row & (0x10 >> column) == 0 {
+                            column += 1;
+                            continue;
+                        }
+                        let run_start = column;
+                        while column < GLYPH_WIDTH && row & (0x10 >> column) != 0 {
+                            column += 1;
+                        }
+                        rects.push(FRect::new(
+                            glyph_x + run_start as f32 * scale,
+                            y,
+                            (column - run_start) as f32 * scale,
+                            scale,
+                        ));
+                    }
+                }
+            }
+        }
+
+        canvas.set_draw_color(color);
+        canvas.fill_frects(rects.as_slice())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::draw_primitives::draw_primitives::{draw_text, fill_circle, text_size, Sprite, TextAlign};
+    use sdl2::pixels::{Color, PixelFormatEnum};
+    use sdl2::rect::{FPoint, Rect};
+    use sdl2::surface::Surface;
+
+    #[test]
+    fn text_size_test() {
+        assert_eq!(text_size("", 1.0), (0.0, 7.0));
+        assert_eq!(text_size("A", 1.0), (5.0, 7.0));
+        assert_eq!(text_size("AB", 2.0), (22.0, 14.0));
+        assert_eq!(text_size("ABC\nD", 1.0), (17.0, 15.0));
+    }
+
+    #[test]
+    fn draw_text_test() {
+        let surface = Surface::new(64, 32, PixelFormatEnum::RGBA32).unwrap();
+        let mut canvas = surface.into_canvas().unwrap();
+        canvas.set_draw_color(Color::BLACK);
+        canvas.clear();
+
+        draw_text(&mut canvas, "1", FPoint::new(32.0, 0.0), 2.0, TextAlign::Center, Color::WHITE).unwrap();
+
+        let pixels = canvas.read_pixels(Rect::new(0, 0, 64, 32), PixelFormatEnum::RGBA32).unwrap();
+        let is_lit = |x: usize, y: usize| pixels[(y * 64 + x) * 4] == 255;
+
+        // the stem of the "1" is the middle column of the glyph, centered on x = 32
+        assert!(is_lit(32, 1));
+        assert!(is_lit(32, 12));
+        // the glyph is 10 pixels wide, nothing is drawn beyond it
+        assert!(!is_lit(26, 12));
+        assert!(!is_lit(38, 12));
+        assert!(!is_lit(32, 20));
+    }
+
+    #[test]
+    fn fill_circle_test() {
+        let surface = Surface::new(64, 64, PixelFormatEnum::RGBA32).unwrap();
+        let mut canvas = surface.into_canvas().unwrap();
+        canvas.set_draw_color(Color::BLACK);
+        canvas.clear();
+
+        canvas.set_draw_color(Color::WHITE);
+        fill_circle(&mut canvas, FPoint::new(32.0, 32.0), 20.0, true).unwrap();
+
+        let pixels = canvas.read_pixels(Rect::new(0, 0, 64, 64), PixelFormatEnum::RGBA32).unwrap();
+        let red_at = |x: usize, y: usize| pixels[(y * 64 + x) * 4];
+
+        assert_eq!(red_at(32, 32), 255);
+        assert_eq!(red_at(32, 14), 255);
+        assert_eq!(red_at(4, 4), 0);
+        // the corner of the bounding box lies outside the circle
+        assert_eq!(red_at(13, 13), 0);
+    }
+
+    #[test]
+    fn sprite_from_art_test() {
+        let sprite = Sprite::from_art(&["#+", "#", ""], &[('#', Color::RED), ('+', Color::WHITE)]);
+
+        assert_eq!((sprite.width, sprite.height), (2, 3));
+        assert_eq!(sprite.pixels[0], Color::RED);
+        assert_eq!(sprite.pixels[1], Color::WHITE);
+        // short rows are padded and unknown or missing characters are transparent
+        assert_eq!(sprite.pixels[2], Color::RED);
+        assert_eq!(sprite.pixels[3], Color::RGBA(0, 0, 0, 0));
+        assert_eq!(sprite.pixels[4..], [Color::RGBA(0, 0, 0, 0); 2]);
+        assert_eq!(Sprite::from_art(&["x"], &[]).pixels, vec![Color::RGBA(0, 0, 0, 0)]);
+    }
+}
@@ -0,0 +1,214 @@
+#![allow(dead_code)]
+
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::{FPoint, Rect};
+use sdl2::render::{BlendMode, Canvas, RenderTarget, Texture, TextureCreator};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ColorStop {
+    pub offset: f32,
+    pub color: Color,
+}
+
+impl ColorStop {
+    pub fn new(offset: f32, color: Color) -> ColorStop {
+        ColorStop {
+            offset,
+            color,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GradientKind {
+    // angle in degrees, 0 runs left to right and positive angles turn clockwise
+    Linear { angle: f32 },
+    // center and radius are relative to the size of the rect the gradient is drawn into
+    Radial { center: FPoint, radius: f32 },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    pub stops: Vec<ColorStop>,
+}
+
+impl Gradient {
+    pub fn linear(angle: f32, stops: Vec<ColorStop>) -> Gradient {
+        Gradient {
+            kind: GradientKind::Linear { angle },
+            stops,
+        }
+    }
+
+    pub fn radial(center: FPoint, radius: f32, stops: Vec<ColorStop>) -> Gradient {
+        Gradient {
+            kind: GradientKind::Radial { center, radius },
+            stops,
+        }
+    }
+
+    pub fn two_color(angle: f32, from: Color, to: Color) -> Gradient {
+        Gradient::linear(angle, vec![ColorStop::new(0.0, from), ColorStop::new(1.0, to)])
+    }
+
+    /// Color of the ramp at `offset`, offsets outside of the stops take the color of the nearest stop.
+    pub fn color_at_offset(&self, offset: f32) -> Color {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Color::RGBA(0, 0, 0, 0),
+        };
+
+        if offset <= first.offset {
+            return first.color;
+        }
+        if offset >= last.offset {
+            return last.color;
+        }
+
+        for pair in self.stops.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            if offset >= from.offset && offset <= to.offset {
+                let span = to.offset - from.offset;
+                let t = if span > 0.0 { (offset - from.offset) / span } else { 1.0 };
+                return lerp_color(from.color, to.color, t);
+            }
+        }
+        last.color
+    }
+
+    /// Color of the pixel at `x`, `y` when the gradient is drawn into a `width` x `height` rect.
+    pub fn color_at(&self, x: f32, y: f32, width: f32, height: f32) -> Color {
+        let offset = match self.kind {
+            GradientKind::Linear { angle } => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                // distance from the center to the furthest corner along the gradient direction
+                let half_extent = ((width * cos).abs() + (height * sin).abs()) / 2.0;
+                let projection = (x - width / 2.0) * cos + (y - height / 2.0) * sin;
+                if half_extent > 0.0 { (projection / half_extent + 1.0) / 2.0 } else { 0.0 }
+            }
+            GradientKind::Radial { center, radius } => {
+                let u = x / width - center.x();
+                let v = y / height - center.y();
+                if radius > 0.0 { u.hypot(v) / radius } else { 1.0 }
+            }
+        };
+        self.color_at_offset(offset)
+    }
+}
+
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Color::RGBA(lerp(from.r, to.r), lerp(from.g, to.g), lerp(from.b, to.b), lerp(from.a, to.a))
+}
+
+/// Renders a `Gradient` into a texture it owns. The texture is regenerated whenever the
+/// gradient or the size of the destination rect changes.
+pub struct GradientTexture<'a, C> {
+    gradient: Gradient,
+    texture_creator: &'a TextureCreator<C>,
+    texture: Option<Texture<'a>>,
+    texture_size: (u32, u32),
+    alpha: u8,
+}
+
+impl<'a, C> GradientTexture<'a, C> {
+    pub fn new(texture_creator: &'a TextureCreator<C>, gradient: Gradient) -> GradientTexture<'a, C> {
+        GradientTexture {
+            gradient,
+            texture_creator,
+            texture: None,
+            texture_size: (0, 0),
+            alpha: 255,
+        }
+    }
+
+    pub fn gradient(&self) -> &Gradient {
+        &self.gradient
+    }
+
+    pub fn set_gradient(&mut self, gradient: Gradient) {
+        if gradient != self.gradient {
+            self.gradient = gradient;
+            self.texture = None;
+        }
+    }
+
+    pub fn set_alpha(&mut self, alpha: u8) {
+        self.alpha = alpha;
+    }
+
+    fn build_texture(&self, width: u32, height: u32) -> Result<Texture<'a>, String> {
+        let mut texture = self.texture_creator
+            .create_texture_static(PixelFormatEnum::RGBA32, width, height)
+            .map_err(|e| e.to_string())?;
+        texture.set_blend_mode(BlendMode::Blend);
+
+        let mut pixels: Vec<u8> = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let color = self.gradient.color_at(x as f32 + 0.5, y as f32 + 0.5, width as f32, height as f32);
+                pixels.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+            }
+        }
+        texture.update(None, pixels.as_slice(), (width * 4) as usize).map_err(|e| e.to_string())?;
+        Ok(texture)
+    }
+
+    pub fn draw<T: RenderTarget>(&mut self, canvas: &mut Canvas<T>, dest_rect: Rect) -> Result<(), String> {
+        let size = (dest_rect.width(), dest_rect.height());
+        if self.texture.is_none() || self.texture_size != size {
+            self.texture = Some(self.build_texture(size.0, size.1)?);
+            self.texture_size = size;
+        }
+
+        if let Some(texture) = self.texture.as_mut() {
+            texture.set_alpha_mod(self.alpha);
+            canvas.copy(texture, None, dest_rect)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gradient::{ColorStop, Gradient};
+    use sdl2::pixels::Color;
+    use sdl2::rect::FPoint;
+
+    #[test]
+    fn color_ramp_test() {
+        let gradient = Gradient::linear(0.0, vec![
+            ColorStop::new(0.0, Color::RGB(0, 0, 0)),
+            ColorStop::new(0.5, Color::RGB(200, 0, 0)),
+            ColorStop::new(1.0, Color::RGB(200, 100, 0)),
+        ]);
+
+        assert_eq!(gradient.color_at_offset(-1.0), Color::RGB(0, 0, 0));
+        assert_eq!(gradient.color_at_offset(0.25), Color::RGB(100, 0, 0));
+        assert_eq!(gradient.color_at_offset(0.75), Color::RGB(200, 50, 0));
+        assert_eq!(gradient.color_at_offset(2.0), Color::RGB(200, 100, 0));
+    }
+
+    #[test]
+    fn linear_angle_test() {
+        let gradient = Gradient::two_color(90.0, Color::RGB(0, 0, 0), Color::RGB(0, 0, 255));
+
+        // 90 degrees runs top to bottom
+        assert_eq!(gradient.color_at(10.0, 0.0, 100.0, 50.0), Color::RGB(0, 0, 0));
+        assert_eq!(gradient.color_at(50.0, 25.0, 100.0, 50.0).b, 128);
+        assert_eq!(gradient.color_at(50.0, 50.0, 100.0, 50.0), Color::RGB(0, 0, 255));
+    }
+
+    #[test]
+    fn radial_test() {
+        let gradient = Gradient::radial(FPoint::new(0.5, 0.5), 0.5, vec![
+            ColorStop::new(0.0, Color::WHITE),
+            ColorStop::new(1.0, Color::BLACK),
+        ]);
+
+        assert_eq!(gradient.color_at(50.0, 50.0, 100.0, 100.0), Color::WHITE);
+        assert_eq!(gradient.color_at(0.0, 50.0, 100.0, 100.0), Color::BLACK);
+        assert_eq!(gradient.color_at(0.0, 0.0, 100.0, 100.0), Color::BLACK);
+    }
+}
@@ -1,16 +1,35 @@
 #![allow(dead_code)]
 #[allow(unused)]
 
-mod draw_primitives;
+pub mod draw_primitives;
+pub mod tick_controller;
+pub mod gradient;
+pub mod theme;
+pub mod particles;
+pub mod post_process;
+pub mod audio;
+pub mod music;
+pub mod screens;
+pub mod ui;
+pub mod net;
+pub mod rollback;
+pub mod spectate;
+pub mod breakout;
+pub mod arena;
 use draw_primitives::draw_primitives::draw_polygon_regular;
+use draw_primitives::draw_primitives::{draw_text, TextAlign};
+use draw_primitives::draw_primitives::{draw_sprite, fill_circle, fill_polygon_regular, fill_rounded_rect};
+use theme::{BallShape, PaddleStyle, Theme};
+use particles::{ParticleEmitter, ParticleSystem};
+use arena::Arena;
+use tick_controller::FrameStats;
 use sdl2::sys::SDL_GetTicks;
 
 use std::f32::consts::PI;
 use std::time;
-use std::time::SystemTime;
-use std::time::UNIX_EPOCH;
 
 use sdl2::event::Event;
+use sdl2::pixels::Color;
 use sdl2::rect::FPoint;
 use sdl2::rect::FRect;
 use sdl2::rect::Point;
@@ -23,6 +42,72 @@ pub struct Ball {
     pub pos: FPoint,
     pub velocity: FPoint,
     pub physics: BallPhysics,
+    // orientation in radians and angular velocity in radians per second, only used for drawing
+    pub rotation: f32,
+    pub spin: f32,
+    pub trail: Trail,
+}
+
+pub const TRAIL_CAPACITY: usize = 24;
+const TRAIL_SAMPLE_INTERVAL: f32 = 1.0 / 60.0;
+
+// ring buffer of past ball positions, fixed size so that Ball stays Copy
+#[derive(Copy, Clone)]
+pub struct Trail {
+    points: [FPoint; TRAIL_CAPACITY],
+    head: usize,
+    len: usize,
+    since_sample: f32,
+}
+
+impl Trail {
+    pub fn new() -> Trail {
+        Trail {
+            points: [FPoint::new(0.0, 0.0); TRAIL_CAPACITY],
+            head: 0,
+            len: 0,
+            since_sample: 0.0,
+        }
+    }
+
+    pub fn push(&mut self, point: FPoint) {
+        self.head = (self.head + 1) % TRAIL_CAPACITY;
+        self.points[self.head] = point;
+        self.len = (self.len + 1).min(TRAIL_CAPACITY);
+    }
+
+    // records `point` if at least one sample interval passed since the last recorded one
+    pub fn sample(&mut self, point: FPoint, delta_t: f32) {
+        self.since_sample += delta_t;
+        if self.since_sample >= TRAIL_SAMPLE_INTERVAL || self.is_empty() {
+            self.since_sample = 0.0;
+            self.push(point);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.since_sample = 0.0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // newest point first
+    pub fn iter(&self) -> impl Iterator<Item = FPoint> + '_ {
+        (0..self.len).map(move |age| self.points[(self.head + TRAIL_CAPACITY - age) % TRAIL_CAPACITY])
+    }
+}
+
+impl Default for Trail {
+    fn default() -> Self {
+        Trail::new()
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -45,22 +130,110 @@ impl PlayField{
             rect: rect,
         }
     }
+
+    // whether `collider` reached the wall on `side`
+    pub fn touches(&self, collider: FRect, side: Side) -> bool {
+        match side {
+            Side::Left => collider.left() <= self.rect.left(),
+            Side::Right => collider.right() >= self.rect.right(),
+            Side::Top => collider.top() <= self.rect.top(),
+            Side::Bottom => collider.bottom() >= self.rect.bottom(),
+        }
+    }
+}
+
+/// Fixed size playfield the game runs in, independent of the window size. It is scaled into the
+/// window keeping its aspect ratio, the leftover space becomes letterbox or pillarbox bars.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct LogicalScreen {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl LogicalScreen {
+    pub fn new(width: u32, height: u32) -> LogicalScreen {
+        LogicalScreen {
+            width,
+            height,
+        }
+    }
+
+    pub fn rect(&self) -> FRect {
+        FRect::new(0.0, 0.0, self.width as f32, self.height as f32)
+    }
+
+    /// Lets SDL do the scaling and letterboxing, mouse events then arrive in logical coordinates.
+    pub fn apply<T: RenderTarget>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String> {
+        canvas.set_logical_size(self.width, self.height).map_err(|e| e.to_string())
+    }
+
+    fn scale(&self, window_size: (u32, u32)) -> f32 {
+        (window_size.0 as f32 / self.width as f32).min(window_size.1 as f32 / self.height as f32)
+    }
+
+    /// The part of a `window_size` window the playfield is drawn into.
+    pub fn letterbox(&self, window_size: (u32, u32)) -> sdl2::rect::Rect {
+        let scale = self.scale(window_size);
+        let width = (self.width as f32 * scale).round() as u32;
+        let height = (self.height as f32 * scale).round() as u32;
+        sdl2::rect::Rect::new(
+            (window_size.0.saturating_sub(width) / 2) as i32,
+            (window_size.1.saturating_sub(height) / 2) as i32,
+            width,
+            height,
+        )
+    }
+
+    /// Maps a point in window coordinates, e.g. from `MouseState`, to the playfield. Points in the
+    /// bars map outside of `rect()`.
+    pub fn to_logical(&self, point: Point, window_size: (u32, u32)) -> FPoint {
+        let letterbox = self.letterbox(window_size);
+        let scale = self.scale(window_size);
+        if scale <= 0.0 {
+            return FPoint::new(0.0, 0.0);
+        }
+        FPoint::new(
+            (point.x() - letterbox.x()) as f32 / scale,
+            (point.y() - letterbox.y()) as f32 / scale,
+        )
+    }
 }
 
 pub trait Drawable {
-    fn draw<T: RenderTarget>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String>;
+    fn draw<T: RenderTarget>(&self, canvas: &mut sdl2::render::Canvas<T>, theme: &Theme) -> Result<(), String>;
 }
 
-impl Drawable for Ball {
-    fn draw<T: RenderTarget>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String> {
+impl Ball {
+    fn draw_trail<T: RenderTarget>(&self, canvas: &mut sdl2::render::Canvas<T>, theme: &Theme) -> Result<(), String> {
+        // slow balls leave short trails, at max_velocity the whole buffer is shown
+        let speed_ratio = (self.velocity.magnitude() / self.physics.max_velocity).clamp(0.0, 1.0);
+        let length = (self.trail.len() as f32 * speed_ratio).round() as usize;
+        let edges = match theme.ball_shape {
+            BallShape::Polygon { edges, .. } => edges,
+            _ => 12,
+        };
 
-        canvas.set_draw_color(sdl2::pixels::Color::GREEN);
+        // skip the newest sample, it is covered by the ball itself; draw the oldest first
+        let points: Vec<FPoint> = self.trail.iter().skip(1).take(length).collect();
+        for (age, point) in points.iter().enumerate().rev() {
+            let fade = 1.0 - (age + 1) as f32 / (length + 1) as f32;
+            let alpha = (theme.ball.a as f32 * fade * 0.5) as u8;
+            canvas.set_draw_color(Color::RGBA(theme.ball.r, theme.ball.g, theme.ball.b, alpha));
+            fill_polygon_regular(canvas, *point, edges, self.size as f32 * (0.5 + 0.5 * fade), self.rotation, theme.antialias)?;
+        }
+        Ok(())
+    }
+}
 
-        enum DrawImp{
-            Dots,
-            Polygon,
+impl Drawable for Ball {
+    fn draw<T: RenderTarget>(&self, canvas: &mut sdl2::render::Canvas<T>, theme: &Theme) -> Result<(), String> {
+
+        if theme.ball_trail {
+            self.draw_trail(canvas, theme)?;
         }
 
+        canvas.set_draw_color(theme.ball);
+
         let draw_dots = |canvas: &mut sdl2::render::Canvas<T>| -> Result<(), String> {
             let mut points: Vec<Point> = Vec::new();
             let resolution = 4;
@@ -83,18 +256,27 @@ impl Drawable for Ball {
             Ok(())
         };
 
-        let draw_poly = |canvas: &mut sdl2::render::Canvas<T>|{
-            let asdf = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_millis() as f32 / 1000.0;
-            let angle_offset = asdf * 2.0 * PI;
-
-            draw_polygon_regular(canvas, self.pos, 7, self.size as f32, angle_offset)
+        let draw_poly = |canvas: &mut sdl2::render::Canvas<T>, edges: u32, filled: bool|{
+            if filled {
+                fill_polygon_regular(canvas, self.pos, edges, self.size as f32, self.rotation, theme.antialias)
+            }
+            else {
+                draw_polygon_regular(canvas, self.pos, edges, self.size as f32, self.rotation)
+            }
         };
 
-        let draw_imp = DrawImp::Polygon;
+        let draw_sprite_ball = |canvas: &mut sdl2::render::Canvas<T>| {
+            match &theme.ball_sprite {
+                Some(sprite) => draw_sprite(canvas, sprite, self.pos, self.size as f32, self.rotation),
+                None => draw_sprite(canvas, &theme.default_ball_sprite(), self.pos, self.size as f32, self.rotation),
+            }
+        };
 
-        match draw_imp {
-            DrawImp::Dots => draw_dots(canvas),
-            DrawImp::Polygon => draw_poly(canvas),
+        match theme.ball_shape {
+            BallShape::Dots => draw_dots(canvas),
+            BallShape::Polygon { edges, filled } => draw_poly(canvas, edges, filled),
+            BallShape::Circle => fill_circle(canvas, self.pos, self.size as f32 / 2.0, theme.antialias),
+            BallShape::Sprite => draw_sprite_ball(canvas),
         }?;
 
         Ok(())
@@ -102,14 +284,19 @@ impl Drawable for Ball {
 }
 
 impl Drawable for Paddle {
-    fn draw<T: RenderTarget>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String> {
-        canvas.set_draw_color(sdl2::pixels::Color::GREEN);
-        canvas.draw_frect(FRect::from_center(self.pos, self.size.x(), self.size.y()))?;
+    fn draw<T: RenderTarget>(&self, canvas: &mut sdl2::render::Canvas<T>, theme: &Theme) -> Result<(), String> {
+        canvas.set_draw_color(theme.paddle);
+        let rect = FRect::from_center(self.pos, self.size.x(), self.size.y());
+        match theme.paddle_style {
+            PaddleStyle::Outline => canvas.draw_frect(rect)?,
+            PaddleStyle::Filled => canvas.fill_frect(rect)?,
+            PaddleStyle::Rounded { radius } => fill_rounded_rect(canvas, rect, radius, theme.antialias)?,
+        }
         Ok(())
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct BallPhysics {
     pub horizontal_acc: f32,
     pub vertical_acc: f32,
@@ -117,6 +304,7 @@ pub struct BallPhysics {
     pub restitution_angle_variance: f32,
     pub inherited_velocity: f32,
     pub max_velocity: f32,
+    pub serve_velocity: f32,
 }
 
 // todo: Unify the Kinematic trait for Ball and Paddle
@@ -137,6 +325,9 @@ impl Kinematic for Ball {
             self.velocity.x() + self.physics.horizontal_acc * tick_interval.as_secs_f32(),
             self.velocity.y() + self.physics.vertical_acc * tick_interval.as_secs_f32(),
         );
+
+        self.rotation = (self.rotation + self.spin * tick_interval.as_secs_f32()).rem_euclid(2.0 * PI);
+        self.trail.sample(self.pos, tick_interval.as_secs_f32());
     }
 
     fn set_velocity(&mut self, velocity: FPoint) {
@@ -148,6 +339,48 @@ impl Kinematic for Ball {
     }
 }
 
+// how far the middle of a paddle sits from its wall
+const PADDLE_WALL_DISTANCE: f32 = 20.0;
+
+impl Paddle {
+    // unit vector along the long side, the paddle moves along it
+    pub fn axis(&self) -> FPoint {
+        if self.size.x() > self.size.y() {
+            FPoint::new(1.0, 0.0)
+        } else {
+            FPoint::new(0.0, 1.0)
+        }
+    }
+
+    // the position of `point` along the axis
+    pub fn along(&self, point: FPoint) -> f32 {
+        let axis = self.axis();
+        point.x() * axis.x() + point.y() * axis.y()
+    }
+
+    pub fn length(&self) -> f32 {
+        self.size.x().max(self.size.y())
+    }
+
+    pub fn set_length(&mut self, length: f32) {
+        if self.size.x() > self.size.y() {
+            self.size = FPoint::new(length, self.size.y());
+        } else {
+            self.size = FPoint::new(self.size.x(), length);
+        }
+    }
+
+    // moves the paddle to its wall, leaving the position along the wall alone
+    pub fn pin_to_wall(&mut self, side: Side, field: FRect) {
+        match side {
+            Side::Left => self.pos.x = field.left() + PADDLE_WALL_DISTANCE,
+            Side::Right => self.pos.x = field.right() - PADDLE_WALL_DISTANCE,
+            Side::Top => self.pos.y = field.top() + PADDLE_WALL_DISTANCE,
+            Side::Bottom => self.pos.y = field.bottom() - PADDLE_WALL_DISTANCE,
+        }
+    }
+}
+
 impl Kinematic for Paddle {
     fn update(&mut self, tick_interval: std::time::Duration) {
         self.pos = FPoint::new(
@@ -222,30 +455,45 @@ pub trait Collide<T: Collider>: Collider {
 // somehning is off here, the velocity vector after has wrong direction
 impl Collide<Paddle> for Ball{
     fn collide(&mut self, other: &Paddle) {
-
-        let intersection = self.collider().intersection(other.collider());
-        if intersection.is_none() {
+        if !self.bounce_off_rect(other.collider()) {
             return;
         }
 
-        let intersection = intersection.unwrap();
-        
+        // the paddle drags the ball along its surface, which reverses and adds to the spin
+        self.spin = -self.spin + other.along(other.velocity) / (self.size as f32 / 2.0);
+    }
+}
+
+impl Ball {
+    // backs out of `rect` the way the ball came, then bounces off the face it went through,
+    // false when the ball does not touch `rect`
+    pub fn bounce_off_rect(&mut self, rect: FRect) -> bool {
+        let intersection = match self.collider().intersection(rect) {
+            Some(intersection) => intersection,
+            None => return false,
+        };
+
         let velocity_vector = self.velocity.normalize();
-        while self.collider().has_intersection(other.collider()) {
-          self.translate(-velocity_vector );
+        while self.collider().has_intersection(rect) {
+            self.translate(-velocity_vector);
         }
 
         if intersection.width() > intersection.height() {
             self.velocity = FPoint::new(self.velocity.x(), -self.velocity.y());
         }
-        else if intersection.height() >  intersection.width() {
+        else if intersection.height() > intersection.width() {
             self.velocity = FPoint::new(-self.velocity.x(), self.velocity.y());
         }
         else {
             self.velocity = FPoint::new(-self.velocity.x(), -self.velocity.y());
         }
+        true
+    }
 
-        let new_velocity_angle = self.velocity.angle() + (rand::random::<f32>() * std::f32::consts::PI / 2.0 - std::f32::consts::PI / 4.0) * self.physics.restitution_angle_variance;
+    // turns the ball by up to 45 degrees either way scaled by restitution_angle_variance and applies
+    // the restitution, `random` is in 0..1 and comes from the game state so replays bounce the same
+    pub fn deflect(&mut self, random: f32) {
+        let new_velocity_angle = self.velocity.angle() + (random * std::f32::consts::PI / 2.0 - std::f32::consts::PI / 4.0) * self.physics.restitution_angle_variance;
         let new_velocity_magnitude = (self.velocity.magnitude() * self.physics.restitution_factor).clamp(0.0, self.physics.max_velocity);
 
         self.velocity = FPoint::new(
@@ -312,12 +560,266 @@ impl Collide<PlayField> for Paddle{
 }
 
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Side {
+    pub const ALL: [Side; 4] = [Side::Left, Side::Right, Side::Top, Side::Bottom];
+
+    pub fn opposite(&self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+            Side::Top => Side::Bottom,
+            Side::Bottom => Side::Top,
+        }
+    }
+
+    // direction from this wall into the field, in radians
+    pub fn inward_angle(&self) -> f32 {
+        match self {
+            Side::Left => 0.0,
+            Side::Right => PI,
+            Side::Top => PI / 2.0,
+            Side::Bottom => -PI / 2.0,
+        }
+    }
+}
+
+// xorshift64*, small and Copy so it can live in the game state
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct GameRng {
+    state: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> GameRng {
+        // an all zero state would only ever produce zeros
+        GameRng { state: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed } }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // uniform in 0..1
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash, Debug)]
+pub struct Score {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+}
+
+impl Score {
+    pub fn get(&self, side: Side) -> u32 {
+        match side {
+            Side::Left => self.left,
+            Side::Right => self.right,
+            Side::Top => self.top,
+            Side::Bottom => self.bottom,
+        }
+    }
+
+    pub fn add(&mut self, side: Side) {
+        match side {
+            Side::Left => self.left += 1,
+            Side::Right => self.right += 1,
+            Side::Top => self.top += 1,
+            Side::Bottom => self.bottom += 1,
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct PangGameState {
     pub ball: Ball,
     pub paddle_left: Paddle,
     pub paddle_right: Paddle,
+    // the top and bottom walls only defend a goal when they have a paddle
+    pub paddle_top: Option<Paddle>,
+    pub paddle_bottom: Option<Paddle>,
+    // left or right closed off by a solid wall instead of its paddle, for practicing alone
+    pub wall: Option<Side>,
     pub canvas: FRect,
+    pub score: Score,
+    pub rally: u32,
+    // the longest rally since the state was set up
+    pub best_rally: u32,
+    // paddle hits speed the ball up by the restitution factor, without it they only turn it
+    pub speed_ramp: bool,
+    // hard paddle hits freeze the simulation for a moment, can be switched off for accessibility
+    pub hit_stop: bool,
+    // the freeze left over from the last hard hit, part of the state so a rollback replays it
+    pub hit_stop_remaining: time::Duration,
+    pub match_time: time::Duration,
+    pub serve: Side,
+    pub last_impact: Option<FRect>,
+    // the side that touched the ball last, it gets the point when the ball goes into a goal
+    pub last_hit: Option<Side>,
+    // everything random in the simulation draws from here, a copy of the state replays identically
+    pub rng: GameRng,
+}
+
+impl PangGameState {
+    pub fn new(ball: Ball, paddle_left: Paddle, paddle_right: Paddle, canvas: FRect) -> PangGameState {
+        PangGameState {
+            ball,
+            paddle_left,
+            paddle_right,
+            paddle_top: None,
+            paddle_bottom: None,
+            wall: None,
+            canvas,
+            score: Score::default(),
+            rally: 0,
+            best_rally: 0,
+            speed_ramp: true,
+            hit_stop: true,
+            hit_stop_remaining: time::Duration::ZERO,
+            match_time: time::Duration::ZERO,
+            serve: Side::Left,
+            last_impact: None,
+            last_hit: None,
+            rng: GameRng::new(rand::random()),
+        }
+    }
+
+    pub fn is_hit_stopped(&self) -> bool {
+        !self.hit_stop_remaining.is_zero()
+    }
+
+    pub fn paddle(&self, side: Side) -> Option<&Paddle> {
+        if self.wall == Some(side) {
+            return None;
+        }
+        match side {
+            Side::Left => Some(&self.paddle_left),
+            Side::Right => Some(&self.paddle_right),
+            Side::Top => self.paddle_top.as_ref(),
+            Side::Bottom => self.paddle_bottom.as_ref(),
+        }
+    }
+
+    pub fn paddle_mut(&mut self, side: Side) -> Option<&mut Paddle> {
+        if self.wall == Some(side) {
+            return None;
+        }
+        match side {
+            Side::Left => Some(&mut self.paddle_left),
+            Side::Right => Some(&mut self.paddle_right),
+            Side::Top => self.paddle_top.as_mut(),
+            Side::Bottom => self.paddle_bottom.as_mut(),
+        }
+    }
+
+    // the sides that have a paddle, and so a goal
+    pub fn sides(&self) -> impl Iterator<Item = Side> + '_ {
+        Side::ALL.into_iter().filter(move |side| self.paddle(*side).is_some())
+    }
+
+    /// Puts a paddle on the top or bottom wall, a copy of the left one turned to lie along it.
+    /// Left and right keep theirs while walled off and get them back here.
+    pub fn add_paddle(&mut self, side: Side) {
+        if self.wall == Some(side) {
+            self.wall = None;
+        }
+        let mut paddle = self.paddle_left;
+        paddle.size = FPoint::new(self.paddle_left.size.y(), self.paddle_left.size.x());
+        paddle.velocity = FPoint::new(0.0, 0.0);
+        paddle.pos = self.canvas.center();
+        paddle.pin_to_wall(side, self.canvas);
+        match side {
+            Side::Top => self.paddle_top = Some(paddle),
+            Side::Bottom => self.paddle_bottom = Some(paddle),
+            Side::Left | Side::Right => {}
+        }
+    }
+
+    /// Turns the wall on `side` into a plain wall the ball bounces off. Only one of left and
+    /// right can be walled off, the other one keeps its paddle.
+    pub fn remove_paddle(&mut self, side: Side) {
+        match side {
+            Side::Top => self.paddle_top = None,
+            Side::Bottom => self.paddle_bottom = None,
+            Side::Left | Side::Right => self.wall = Some(side),
+        }
+    }
+
+    // puts the ball in the middle of the field, moving away from the serving side
+    pub fn serve_ball(&mut self) {
+        let angle = self.serve.inward_angle() + self.rng.next_f32() * PI / 2.0 - PI / 4.0;
+
+        self.ball.pos = self.canvas.center();
+        self.ball.trail.clear();
+        self.ball.velocity = FPoint::new(
+            self.ball.physics.serve_velocity * angle.cos(),
+            self.ball.physics.serve_velocity * angle.sin(),
+        );
+        self.rally = 0;
+        self.last_hit = None;
+    }
+
+    pub fn point_scored(&mut self, scorer: Side) {
+        self.score.add(scorer);
+        self.serve = scorer.opposite();
+        self.serve_ball();
+    }
+
+    /// The ball went into the goal of `conceding`. The point goes to whoever touched the ball
+    /// last, or the opposite side when nobody did, and the conceding side serves. Returns the scorer.
+    pub fn goal_against(&mut self, conceding: Side) -> Side {
+        let scorer = match self.last_hit {
+            Some(side) if side != conceding => side,
+            _ if self.paddle(conceding.opposite()).is_some() => conceding.opposite(),
+            _ => self.sides().find(|side| *side != conceding).unwrap_or(conceding.opposite()),
+        };
+        self.score.add(scorer);
+        self.serve = conceding;
+        self.serve_ball();
+        scorer
+    }
+
+    // extrapolates the ball bouncing off the top and bottom walls until it reaches one of the paddle lines
+    pub fn predict_ball_path(&self, horizon: time::Duration) -> Vec<FPoint> {
+        let step = time::Duration::from_secs_f32(1.0 / 120.0);
+        let mut ball = self.ball;
+        let mut path = vec![ball.pos];
+        let mut elapsed = time::Duration::ZERO;
+
+        let half_size = ball.size as f32 / 2.0;
+        let top = self.canvas.top() + half_size;
+        let bottom = self.canvas.bottom() - half_size;
+
+        while elapsed < horizon {
+            ball.update(step);
+            elapsed += step;
+
+            if ball.pos.y() < top || ball.pos.y() > bottom {
+                ball.pos.y = ball.pos.y().clamp(top, bottom);
+                ball.velocity = FPoint::new(ball.velocity.x(), -ball.velocity.y());
+            }
+            path.push(ball.pos);
+
+            if ball.pos.x() <= self.paddle_left.pos.x() || ball.pos.x() >= self.paddle_right.pos.x() {
+                break;
+            }
+        }
+        path
+    }
 }
 
 pub trait GameController<State>{
@@ -325,9 +827,159 @@ pub trait GameController<State>{
     fn draw<T: RenderTarget>(&self, game_state: &State, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String>;
 }
 
+// things that happened during a tick, for effects that live outside of the game state
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GameEvent {
+    PaddleHit { side: Side, pos: FPoint, speed: f32 },
+    WallBounce { pos: FPoint, normal: FPoint, speed: f32 },
+    Goal { scorer: Side, pos: FPoint },
+    // the ball went out where nobody scores from it, like the bottom of the breakout field
+    BallLost { pos: FPoint },
+}
+
+const MAX_SCREEN_SHAKE: f32 = 12.0;
+const SCREEN_SHAKE_DECAY: f32 = 10.0;
+// paddle hits below this fraction of max_velocity never freeze the game
+const HIT_STOP_THRESHOLD: f32 = 0.5;
+const MAX_HIT_STOP: f32 = 0.1;
+
+// how long a paddle hit freezes the game, strength is the impact speed relative to the
+// ball's max_velocity, 0 to 1
+fn hit_stop_duration(strength: f32) -> time::Duration {
+    let strength = strength.clamp(0.0, 1.0);
+    if strength <= HIT_STOP_THRESHOLD {
+        return time::Duration::ZERO;
+    }
+    time::Duration::from_secs_f32((strength - HIT_STOP_THRESHOLD) / (1.0 - HIT_STOP_THRESHOLD) * MAX_HIT_STOP)
+}
+
+// screen shake, only moves the picture so it can be switched off for accessibility
+pub struct ScreenEffects {
+    pub screen_shake: bool,
+    shake: f32,
+    shake_offset: FPoint,
+}
+
+impl ScreenEffects {
+    pub fn new() -> ScreenEffects {
+        ScreenEffects {
+            screen_shake: true,
+            shake: 0.0,
+            shake_offset: FPoint::new(0.0, 0.0),
+        }
+    }
+
+    // strength is the impact speed relative to the ball's max_velocity, 0 to 1
+    pub fn impact(&mut self, strength: f32) {
+        let strength = strength.clamp(0.0, 1.0);
+        if self.screen_shake {
+            self.shake = self.shake.max(MAX_SCREEN_SHAKE * strength);
+        }
+    }
+
+    pub fn update(&mut self, delta_t: time::Duration) {
+        self.shake *= (-SCREEN_SHAKE_DECAY * delta_t.as_secs_f32()).exp();
+        if !self.screen_shake || self.shake < 0.5 {
+            self.shake = 0.0;
+        }
+        let angle = rand::random::<f32>() * 2.0 * PI;
+        self.shake_offset = FPoint::new(self.shake * angle.cos(), self.shake * angle.sin());
+    }
+
+    pub fn shake_offset(&self) -> FPoint {
+        self.shake_offset
+    }
+}
+
+impl Default for ScreenEffects {
+    fn default() -> Self {
+        ScreenEffects::new()
+    }
+}
+
 pub struct PangGameController{
     pub paddle_controller_left: Box<dyn PaddleController>, 
     pub paddle_controller_right: Box<dyn PaddleController>,
+    // only asked while the state has a paddle on that wall
+    pub paddle_controller_top: Option<Box<dyn PaddleController>>,
+    pub paddle_controller_bottom: Option<Box<dyn PaddleController>>,
+    pub theme: Theme,
+    // events accumulate until they are taken with `take_events`
+    pub events: Vec<GameEvent>,
+    // None disables particles entirely
+    pub particles: Option<ParticleSystem>,
+    pub effects: ScreenEffects,
+    // obstacles and portals on the field, None plays on the plain field
+    pub arena: Option<Arena>,
+}
+
+impl PangGameController {
+    pub fn new(paddle_controller_left: Box<dyn PaddleController>, paddle_controller_right: Box<dyn PaddleController>) -> PangGameController {
+        PangGameController {
+            paddle_controller_left,
+            paddle_controller_right,
+            paddle_controller_top: None,
+            paddle_controller_bottom: None,
+            theme: Theme::default_theme(),
+            events: Vec::new(),
+            particles: None,
+            effects: ScreenEffects::new(),
+            arena: None,
+        }
+    }
+
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn paddle_controller(&self, side: Side) -> Option<&dyn PaddleController> {
+        match side {
+            Side::Left => Some(self.paddle_controller_left.as_ref()),
+            Side::Right => Some(self.paddle_controller_right.as_ref()),
+            Side::Top => self.paddle_controller_top.as_deref(),
+            Side::Bottom => self.paddle_controller_bottom.as_deref(),
+        }
+    }
+
+    fn paddle_controller_mut(&mut self, side: Side) -> Option<&mut Box<dyn PaddleController>> {
+        match side {
+            Side::Left => Some(&mut self.paddle_controller_left),
+            Side::Right => Some(&mut self.paddle_controller_right),
+            Side::Top => self.paddle_controller_top.as_mut(),
+            Side::Bottom => self.paddle_controller_bottom.as_mut(),
+        }
+    }
+
+    pub fn set_paddle_controller(&mut self, side: Side, controller: Box<dyn PaddleController>) {
+        match side {
+            Side::Left => self.paddle_controller_left = controller,
+            Side::Right => self.paddle_controller_right = controller,
+            Side::Top => self.paddle_controller_top = Some(controller),
+            Side::Bottom => self.paddle_controller_bottom = Some(controller),
+        }
+    }
+
+    fn spawn_particles(&mut self, events_from: usize) {
+        let particles = match self.particles.as_mut() {
+            Some(particles) => particles,
+            None => return,
+        };
+
+        for event in &self.events[events_from..] {
+            match *event {
+                GameEvent::PaddleHit { side, pos, .. } => {
+                    particles.burst(&ParticleEmitter::sparks(self.theme.ball), pos, side.inward_angle());
+                }
+                GameEvent::WallBounce { pos, normal, .. } => {
+                    particles.burst(&ParticleEmitter::dust(self.theme.ball), pos, normal.angle());
+                }
+                GameEvent::Goal { pos, .. } => {
+                    particles.burst(&ParticleEmitter::firework(self.theme.hud), pos, 0.0);
+                }
+                GameEvent::BallLost { .. } => {}
+            }
+        }
+    }
 }
 
 impl GameController<PangGameState>  for PangGameController{
@@ -335,44 +987,243 @@ impl GameController<PangGameState>  for PangGameController{
         
         // todo: remove clone and buikd the state in a more functional way  
         let mut game_state = game_state_ref.clone();
+        let play_field = PlayField::from_rect(game_state.canvas);
+        let wall = game_state.wall;
         let ball = &mut game_state.ball;
-        let paddle_left = &mut game_state.paddle_left;
-        let paddle_right = &mut game_state.paddle_right;
-
-        paddle_left.pos.x = game_state.canvas.left() + 20.0;
-        paddle_right.pos.x = game_state.canvas.right() - 20.0;
+        let mut paddles: Vec<(Side, &mut Paddle)> = [
+            (Side::Left, Some(&mut game_state.paddle_left)),
+            (Side::Right, Some(&mut game_state.paddle_right)),
+            (Side::Top, game_state.paddle_top.as_mut()),
+            (Side::Bottom, game_state.paddle_bottom.as_mut()),
+        ].into_iter().filter(|(side, _)| wall != Some(*side)).filter_map(|(side, paddle)| paddle.map(|paddle| (side, paddle))).collect();
+
+        for (side, paddle) in paddles.iter_mut() {
+            paddle.pin_to_wall(*side, play_field.rect);
+            if let Some(controller) = self.paddle_controller_mut(*side) {
+                controller.update_paddle(game_state_ref, &event, paddle);
+            }
+        }
 
-        self.paddle_controller_left.update_paddle(game_state_ref, &event, paddle_left);
-        self.paddle_controller_right.update_paddle(game_state_ref, &event, paddle_right);
+        // controllers keep reading input, but nothing moves while the hit-stop lasts
+        if !game_state.hit_stop_remaining.is_zero() {
+            game_state.hit_stop_remaining = game_state.hit_stop_remaining.saturating_sub(delta_t);
+            self.effects.update(delta_t);
+            return Ok(game_state);
+        }
 
         ball.update(delta_t);
-        paddle_left.update(delta_t);
-        paddle_right.update(delta_t);
+        for (_, paddle) in paddles.iter_mut() {
+            paddle.update(delta_t);
+        }
 
-        let play_field = PlayField::from_rect(game_state.canvas);
+        // the first paddle the ball overlaps gets the hit
+        let hit = paddles.iter().find_map(|(side, paddle)| ball.collider().intersection(paddle.collider()).map(|impact| (*side, impact)));
+
+        for (_, paddle) in paddles.iter() {
+            ball.collide(&**paddle);
+        }
+        if hit.is_some() {
+            let speed = ball.velocity.magnitude();
+            ball.deflect(game_state.rng.next_f32());
+            if !game_state.speed_ramp {
+                ball.velocity = ball.velocity.normalize() * speed;
+            }
+        }
+
+        let events_from = self.events.len();
+        if let Some((side, impact)) = hit {
+            self.events.push(GameEvent::PaddleHit {
+                side,
+                pos: impact.center(),
+                speed: ball.velocity.magnitude(),
+            });
+            let strength = ball.velocity.magnitude() / ball.physics.max_velocity;
+            self.effects.impact(strength);
+            if game_state.hit_stop {
+                game_state.hit_stop_remaining = game_state.hit_stop_remaining.max(hit_stop_duration(strength));
+            }
+        }
+
+        // moving obstacles go by the match time, so replays and the other side of a network
+        // match see them in the same place
+        if let Some(arena) = &self.arena {
+            for normal in arena.collide(ball, game_state.match_time) {
+                self.events.push(GameEvent::WallBounce {
+                    pos: ball.pos - normal * (ball.size as f32 / 2.0),
+                    normal,
+                    speed: ball.velocity.magnitude(),
+                });
+            }
+        }
+
+        // walls with a paddle are goals, the others bounce
+        let conceding = paddles.iter().map(|(side, _)| *side).find(|side| play_field.touches(ball.collider(), *side));
 
-        ball.collide(paddle_left);
-        ball.collide(paddle_right);
+        let velocity_before_walls = ball.velocity;
         ball.collide(&play_field);
+
+        if conceding.is_none() && ball.velocity.y().signum() != velocity_before_walls.y().signum() {
+            let normal = if ball.pos.y() < play_field.rect.center().y() { FPoint::new(0.0, 1.0) } else { FPoint::new(0.0, -1.0) };
+            self.events.push(GameEvent::WallBounce {
+                pos: FPoint::new(ball.pos.x(), ball.pos.y() - normal.y() * ball.size as f32 / 2.0),
+                normal,
+                speed: ball.velocity.magnitude(),
+            });
+        }
+        // a walled off side bounces the ball back like the top and bottom
+        if conceding.is_none() && ball.velocity.x().signum() != velocity_before_walls.x().signum() {
+            let normal = if ball.pos.x() < play_field.rect.center().x() { FPoint::new(1.0, 0.0) } else { FPoint::new(-1.0, 0.0) };
+            self.events.push(GameEvent::WallBounce {
+                pos: FPoint::new(ball.pos.x() - normal.x() * ball.size as f32 / 2.0, ball.pos.y()),
+                normal,
+                speed: ball.velocity.magnitude(),
+            });
+        }
         
-        paddle_left.collide(&play_field);
-        paddle_right.collide(&play_field);
+        for (_, paddle) in paddles.iter_mut() {
+            paddle.collide(&play_field);
+        }
+
+        game_state.match_time += delta_t;
+        if let Some((side, impact)) = hit {
+            game_state.rally += 1;
+            game_state.best_rally = game_state.best_rally.max(game_state.rally);
+            game_state.last_impact = Some(impact);
+            game_state.last_hit = Some(side);
+        }
+        if let Some(conceding) = conceding {
+            let pos = game_state.ball.pos;
+            let scorer = game_state.goal_against(conceding);
+            self.events.push(GameEvent::Goal { scorer, pos });
+            self.effects.impact(1.0);
+        }
+        game_state.hit_stop_remaining = game_state.hit_stop_remaining.saturating_sub(delta_t);
+        self.effects.update(delta_t);
+
+        self.spawn_particles(events_from);
+        if let Some(particles) = self.particles.as_mut() {
+            particles.update(delta_t);
+        }
 
         Ok(game_state)
     }
     
     fn draw<T: RenderTarget>(&self, game_state: &PangGameState, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String> {
-        game_state.ball.draw(canvas)?;
-        game_state.paddle_left.draw(canvas)?;
-        game_state.paddle_right.draw(canvas)?;
+        // screen shake moves the viewport, restore it even if drawing fails
+        let viewport = canvas.viewport();
+        let offset = self.effects.shake_offset();
+        canvas.set_viewport(sdl2::rect::Rect::new(
+            viewport.x() + offset.x().round() as i32,
+            viewport.y() + offset.y().round() as i32,
+            viewport.width(),
+            viewport.height(),
+        ));
+
+        let result = self.draw_scene(game_state, canvas);
+        canvas.set_viewport(viewport);
+        result
+    }
+}
+
+
+impl PangGameController {
+    fn draw_scene<T: RenderTarget>(&self, game_state: &PangGameState, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String> {
+        self.draw_hud(game_state, canvas)?;
+        if let Some(arena) = &self.arena {
+            arena.draw(canvas, &self.theme, game_state.match_time)?;
+        }
+        if let Some(particles) = &self.particles {
+            particles.draw(canvas)?;
+        }
+        game_state.ball.draw(canvas, &self.theme)?;
+        for side in game_state.sides() {
+            if let Some(paddle) = game_state.paddle(side) {
+                paddle.draw(canvas, &self.theme)?;
+            }
+        }
+        if let Some(wall) = game_state.wall {
+            let field = game_state.canvas;
+            let thickness = 8.0;
+            let x = if wall == Side::Left { field.left() } else { field.right() - thickness };
+            canvas.set_draw_color(self.theme.paddle);
+            canvas.fill_frect(FRect::new(x, field.top(), thickness, field.height()))?;
+        }
         
         Ok(())
     }
-}
 
+    fn draw_hud<T: RenderTarget>(&self, game_state: &PangGameState, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String> {
+        let color = self.theme.hud;
+        let field = game_state.canvas;
+        let center_x = field.center().x();
+
+        // everything is laid out for a 600 units high field and scaled from there
+        let unit = field.height() / 600.0;
+        let top_band = 30.0 * unit;
+        let bottom_band = 40.0 * unit;
+
+        let dash_length = 20.0 * unit;
+        let dash_width = 4.0 * unit;
+        let mut dashes: Vec<FRect> = Vec::new();
+        let mut dash_y = field.top() + top_band;
+        // a practice court has no net, the rally count sits in the middle instead
+        while game_state.wall.is_none() && dash_y + dash_length < field.bottom() - bottom_band {
+            dashes.push(FRect::new(center_x - dash_width / 2.0, dash_y, dash_width, dash_length));
+            dash_y += dash_length * 2.0;
+        }
+        canvas.set_draw_color(color);
+        canvas.fill_frects(dashes.as_slice())?;
+
+        let minutes = game_state.match_time.as_secs() / 60;
+        let seconds = game_state.match_time.as_secs() % 60;
+        draw_text(canvas, &format!("{:02}:{:02}", minutes, seconds), FPoint::new(center_x, field.top() + 8.0 * unit), 2.0 * unit, TextAlign::Center, color)?;
+
+        let score_scale = 8.0 * unit;
+        let score_y = field.top() + top_band + 10.0 * unit;
+        let score_gap = 30.0 * unit;
+        if let Some(wall) = game_state.wall {
+            // the wall gets a point for every miss
+            draw_text(canvas, &game_state.rally.to_string(), FPoint::new(center_x, score_y), score_scale, TextAlign::Center, color)?;
+            let practice = format!("BEST {}  MISSES {}", game_state.best_rally, game_state.score.get(wall));
+            draw_text(canvas, &practice, FPoint::new(center_x, score_y + 110.0 * unit), 2.0 * unit, TextAlign::Center, color)?;
+        } else {
+            draw_text(canvas, &game_state.score.left.to_string(), FPoint::new(center_x - score_gap, score_y), score_scale, TextAlign::Right, color)?;
+            draw_text(canvas, &game_state.score.right.to_string(), FPoint::new(center_x + score_gap, score_y), score_scale, TextAlign::Left, color)?;
+        }
+
+        // top and bottom only have a score while they have a paddle, it sits next to their wall
+        let top_score_y = field.top() + top_band + 100.0 * unit;
+        let bottom_score_y = field.bottom() - bottom_band - 80.0 * unit;
+        if game_state.paddle_top.is_some() {
+            draw_text(canvas, &game_state.score.top.to_string(), FPoint::new(center_x, top_score_y), 4.0 * unit, TextAlign::Center, color)?;
+        }
+        if game_state.paddle_bottom.is_some() {
+            draw_text(canvas, &game_state.score.bottom.to_string(), FPoint::new(center_x, bottom_score_y), 4.0 * unit, TextAlign::Center, color)?;
+        }
+
+        let serve_y = score_y + 70.0 * unit;
+        match game_state.serve {
+            Side::Left => draw_text(canvas, "SERVE", FPoint::new(center_x - score_gap, serve_y), 2.0 * unit, TextAlign::Right, color)?,
+            Side::Right => draw_text(canvas, "SERVE", FPoint::new(center_x + score_gap, serve_y), 2.0 * unit, TextAlign::Left, color)?,
+            Side::Top => draw_text(canvas, "SERVE", FPoint::new(center_x, top_score_y + 35.0 * unit), 2.0 * unit, TextAlign::Center, color)?,
+            Side::Bottom => draw_text(canvas, "SERVE", FPoint::new(center_x, bottom_score_y - 20.0 * unit), 2.0 * unit, TextAlign::Center, color)?,
+        }
+
+        let stats_y = field.bottom() - 25.0 * unit;
+        draw_text(canvas, &format!("RALLY {}", game_state.rally), FPoint::new(center_x - score_gap, stats_y), 2.0 * unit, TextAlign::Right, color)?;
+        draw_text(canvas, &format!("SPEED {:.0}", game_state.ball.velocity.magnitude()), FPoint::new(center_x + score_gap, stats_y), 2.0 * unit, TextAlign::Left, color)?;
+
+        Ok(())
+    }
+}
 
 pub trait PaddleController{
     fn update_paddle(&mut self, game_state: &PangGameState, event: &Event, paddle: &mut Paddle);
+
+    // the position along the paddle's axis the controller is currently steering towards, if it has one
+    fn target(&self) -> Option<f32> {
+        None
+    }
 }
 
 
@@ -437,53 +1288,157 @@ impl PaddleController for PlayerPaddleController {
             _ => {}
         }
 
+        // up and down move towards the start and the end of the paddle's axis, left and right
+        // for paddles on the top and bottom walls
         if self.down_btn_pressed == self.up_btn_pressed {
             paddle.velocity = FPoint::new(0.0, 0.0);
         }
         else if self.down_btn_pressed {
-            paddle.velocity = FPoint::new(0.0, 1.0) * paddle.movement_speed;
+            paddle.velocity = paddle.axis() * paddle.movement_speed;
         }
         else if self.up_btn_pressed {
-            paddle.velocity = FPoint::new(0.0, -1.0) * paddle.movement_speed;
+            paddle.velocity = paddle.axis() * -paddle.movement_speed;
         }
     }
 }
 
 pub struct PaddleAIController{
+    target: Option<f32>,
 }
 
 impl PaddleAIController {
     pub fn new() -> PaddleAIController {
         PaddleAIController {
+            target: None,
         }
     }
 }
 
 impl PaddleController for PaddleAIController {
     fn update_paddle(&mut self, game_state: &PangGameState, _event: &Event, paddle: &mut Paddle) {
+        // everything is measured along the paddle's axis and across it, towards its wall
+        let axis = paddle.axis();
+        let across = |point: FPoint| point.x() * axis.y() + point.y() * axis.x();
 
-        let mut target_speed_y: f32;
-        let target_y: f32;
-        let is_ball_moving_towards_paddle = game_state.ball.velocity.x().signum() == (paddle.pos.x()-game_state.ball.pos.x()).signum();
+        let target_speed: f32;
+        let target: f32;
+        let is_ball_moving_towards_paddle = across(game_state.ball.velocity).signum() == (across(paddle.pos) - across(game_state.ball.pos)).signum();
         
 
         if is_ball_moving_towards_paddle {
-            target_y = game_state.ball.pos.y();
-            target_speed_y = game_state.ball.velocity.y();
+            target = paddle.along(game_state.ball.pos);
+            target_speed = paddle.along(game_state.ball.velocity);
         }
         else{
-            target_y = game_state.canvas.center().y();
-            target_speed_y = 0.0;
+            target = paddle.along(game_state.canvas.center());
+            target_speed = 0.0;
         }
 
-        let on_target = (paddle.pos.y() - target_y).abs() < paddle.size.y() / 4.0; 
+        self.target = Some(target);
+
+        let on_target = (paddle.along(paddle.pos) - target).abs() < paddle.length() / 4.0; 
 
         if on_target {
-            paddle.velocity = FPoint::new(0.0, target_speed_y);
+            paddle.velocity = axis * target_speed;
         }
         else{
-            paddle.velocity = FPoint::new(0.0, (target_y - paddle.pos.y()).signum() * paddle.movement_speed);
+            paddle.velocity = axis * ((target - paddle.along(paddle.pos)).signum() * paddle.movement_speed);
+        }
+    }
+
+    fn target(&self) -> Option<f32> {
+        self.target
+    }
+}
+
+#[derive(Default)]
+pub struct DebugOverlay {
+    pub enabled: bool,
+    // mouse position in logical coordinates
+    pub cursor: Option<FPoint>,
+}
+
+impl DebugOverlay {
+    pub fn new() -> DebugOverlay {
+        DebugOverlay {
+            enabled: false,
+            cursor: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    pub fn draw<T: RenderTarget>(&self, game_state: &PangGameState, game_controller: &PangGameController, frame_stats: &FrameStats, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        // velocity vectors are drawn as the distance covered in this much time
+        let velocity_scale = 0.2;
+        let ball = &game_state.ball;
+        let paddles: Vec<&Paddle> = game_state.sides().filter_map(|side| game_state.paddle(side)).collect();
+
+        canvas.set_draw_color(Color::YELLOW);
+        canvas.draw_frect(ball.collider())?;
+        for paddle in paddles.iter() {
+            canvas.draw_frect(paddle.collider())?;
+        }
+        canvas.draw_frect(PlayField::from_rect(game_state.canvas).collider())?;
+
+        if let Some(impact) = game_state.last_impact {
+            canvas.set_draw_color(Color::RED);
+            canvas.fill_frect(impact)?;
+        }
+
+        canvas.set_draw_color(Color::CYAN);
+        canvas.draw_fline(ball.pos, ball.pos + ball.velocity * velocity_scale)?;
+        for paddle in paddles.iter() {
+            canvas.draw_fline(paddle.pos, paddle.pos + paddle.velocity * velocity_scale)?;
         }
+
+        canvas.set_draw_color(Color::WHITE);
+        let path = game_state.predict_ball_path(time::Duration::from_secs(3));
+        canvas.draw_flines(path.as_slice())?;
+
+        canvas.set_draw_color(Color::MAGENTA);
+        for side in game_state.sides() {
+            let (paddle, controller) = match (game_state.paddle(side), game_controller.paddle_controller(side)) {
+                (Some(paddle), Some(controller)) => (paddle, controller),
+                _ => continue,
+            };
+            if let Some(target) = controller.target() {
+                // a short line across the paddle's track where it is headed
+                let axis = paddle.axis();
+                let across = FPoint::new(axis.y(), axis.x());
+                let reach = paddle.size.x().min(paddle.size.y());
+                let point = paddle.pos + axis * (target - paddle.along(paddle.pos));
+                canvas.draw_fline(point - across * reach, point + across * reach)?;
+            }
+        }
+
+        let mut stats = format!(
+            "FPS {:.0}/{}\nBUSY {:.2}MS\nTICK {:.2}MS\nSKIPPED {}\nBALL {:.0},{:.0}",
+            frame_stats.fps,
+            frame_stats.target_fps,
+            frame_stats.busy_duration.as_secs_f32() * 1000.0,
+            frame_stats.tick_interval.as_secs_f32() * 1000.0,
+            frame_stats.skipped_frames,
+            ball.pos.x(),
+            ball.pos.y(),
+        );
+
+        if let Some(cursor) = self.cursor {
+            let reach = 8.0;
+            canvas.set_draw_color(Color::YELLOW);
+            canvas.draw_fline(cursor - FPoint::new(reach, 0.0), cursor + FPoint::new(reach, 0.0))?;
+            canvas.draw_fline(cursor - FPoint::new(0.0, reach), cursor + FPoint::new(0.0, reach))?;
+            stats.push_str(&format!("\nCURSOR {:.0},{:.0}", cursor.x(), cursor.y()));
+        }
+        draw_text(canvas, &stats, FPoint::new(game_state.canvas.left() + 40.0, game_state.canvas.top() + 40.0), 2.0, TextAlign::Left, Color::YELLOW)?;
+
+        Ok(())
     }
 }
 
@@ -501,3 +1456,300 @@ trait PaddleMover {
         paddle.velocity = FPoint::new(0.0, 0.0);
     }
 }
+
+// fixtures shared by the tests of every module
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+    use crate::screens::ScreenFlow;
+    use sdl2::keyboard::Mod;
+
+    pub fn key(keycode: Keycode) -> Event {
+        Event::KeyDown { timestamp: 0, window_id: 0, keycode: Some(keycode), scancode: None, keymod: Mod::NOMOD, repeat: false }
+    }
+
+    // for updates that should only let time pass
+    pub fn no_event() -> Event {
+        Event::Unknown { timestamp: 0, type_: 0 }
+    }
+
+    // resting in the middle of the field, without gravity or random bounces
+    pub fn test_ball() -> Ball {
+        let physics = BallPhysics {
+            horizontal_acc: 0.0,
+            vertical_acc: 0.0,
+            restitution_factor: 1.0,
+            restitution_angle_variance: 0.0,
+            inherited_velocity: 0.0,
+            max_velocity: 1000.0,
+            serve_velocity: 250.0,
+        };
+        Ball {
+            size: 20,
+            pos: FPoint::new(400.0, 300.0),
+            velocity: FPoint::new(0.0, 0.0),
+            physics,
+            rotation: 0.0,
+            spin: 0.0,
+            trail: Trail::new(),
+        }
+    }
+
+    pub fn test_paddle() -> Paddle {
+        Paddle {
+            size: FPoint::new(20.0, 100.0),
+            pos: FPoint::new(0.0, 300.0),
+            velocity: FPoint::new(0.0, 0.0),
+            acceleration: FPoint::new(0.0, 0.0),
+            movement_speed: 500.0,
+        }
+    }
+
+    // an 800x600 field with paddles on the left and right
+    pub fn test_state() -> PangGameState {
+        PangGameState::new(test_ball(), test_paddle(), test_paddle(), FRect::new(0.0, 0.0, 800.0, 600.0))
+    }
+
+    pub fn test_flow() -> ScreenFlow {
+        test_flow_with(test_state())
+    }
+
+    // for tests that need other physics than the defaults, new matches start from `state`
+    pub fn test_flow_with(state: PangGameState) -> ScreenFlow {
+        let controller = PangGameController::new(Box::new(PaddleAIController::new()), Box::new(PaddleAIController::new()));
+        ScreenFlow::new(state, controller)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{no_event, test_state};
+
+    fn test_controller() -> PangGameController {
+        PangGameController::new(
+            Box::new(PlayerPaddleController::new(Keycode::W, Keycode::S)),
+            Box::new(PlayerPaddleController::new(Keycode::Up, Keycode::Down)),
+        )
+    }
+
+    #[test]
+    fn goal_scores_and_serves_test() {
+        let mut state = test_state();
+        state.ball.pos = FPoint::new(10.0, 50.0);
+        state.ball.velocity = FPoint::new(-100.0, 0.0);
+        state.rally = 5;
+
+        let state = test_controller().update(&state, time::Instant::now(), time::Duration::from_millis(10), no_event()).unwrap();
+
+        assert_eq!(state.score, Score { left: 0, right: 1, top: 0, bottom: 0 });
+        assert_eq!(state.serve, Side::Left);
+        assert_eq!(state.rally, 0);
+        assert_eq!(state.ball.pos, state.canvas.center());
+        assert!(state.ball.velocity.x() > 0.0);
+    }
+
+    #[test]
+    fn four_way_goal_scores_for_last_hitter_test() {
+        let mut state = test_state();
+        state.add_paddle(Side::Top);
+        state.add_paddle(Side::Bottom);
+        state.last_hit = Some(Side::Right);
+        state.ball.pos = FPoint::new(700.0, 10.0);
+        state.ball.velocity = FPoint::new(0.0, -100.0);
+        let mut controller = test_controller();
+        controller.set_paddle_controller(Side::Top, Box::new(PaddleAIController::new()));
+        controller.set_paddle_controller(Side::Bottom, Box::new(PaddleAIController::new()));
+
+        let state = controller.update(&state, time::Instant::now(), time::Duration::from_millis(10), no_event()).unwrap();
+
+        assert_eq!(state.score, Score { left: 0, right: 1, top: 0, bottom: 0 });
+        assert_eq!(state.serve, Side::Top);
+        assert!(state.ball.velocity.y() > 0.0);
+    }
+
+    #[test]
+    fn ai_follows_ball_along_top_wall_test() {
+        let mut state = test_state();
+        state.add_paddle(Side::Top);
+        state.ball.pos = FPoint::new(650.0, 300.0);
+        state.ball.velocity = FPoint::new(0.0, -100.0);
+        let mut paddle = *state.paddle(Side::Top).unwrap();
+
+        let mut ai = PaddleAIController::new();
+        ai.update_paddle(&state, &no_event(), &mut paddle);
+
+        assert_eq!(ai.target(), Some(650.0));
+        assert!(paddle.velocity.x() > 0.0);
+        assert_eq!(paddle.velocity.y(), 0.0);
+    }
+
+    #[test]
+    fn walled_off_side_bounces_test() {
+        let mut state = test_state();
+        state.remove_paddle(Side::Right);
+        assert_eq!(state.sides().collect::<Vec<Side>>(), vec![Side::Left]);
+        state.ball.pos = FPoint::new(790.0, 300.0);
+        state.ball.velocity = FPoint::new(100.0, 0.0);
+        let mut controller = test_controller();
+
+        let state = controller.update(&state, time::Instant::now(), time::Duration::from_millis(10), no_event()).unwrap();
+
+        assert_eq!(state.score, Score::default());
+        assert!(state.ball.velocity.x() < 0.0);
+        assert!(matches!(controller.take_events()[..], [GameEvent::WallBounce { .. }]));
+    }
+
+    #[test]
+    fn arena_obstacle_bounces_test() {
+        let mut state = test_state();
+        state.ball.pos = FPoint::new(352.0, 300.0);
+        state.ball.velocity = FPoint::new(100.0, 0.0);
+        let mut controller = test_controller();
+        controller.arena = Some(Arena::parse("obstacle = circle 400 300 40").unwrap());
+
+        let state = controller.update(&state, time::Instant::now(), time::Duration::from_millis(10), no_event()).unwrap();
+
+        assert!(state.ball.velocity.x() < 0.0);
+        assert!(matches!(controller.take_events()[..], [GameEvent::WallBounce { .. }]));
+    }
+
+    #[test]
+    fn speed_ramp_and_best_rally_test() {
+        let mut state = test_state();
+        state.ball.physics.restitution_factor = 1.5;
+        state.speed_ramp = false;
+        state.rally = 3;
+        state.best_rally = 2;
+        state.ball.pos = FPoint::new(40.0, 300.0);
+        state.ball.velocity = FPoint::new(-100.0, 0.0);
+
+        let state = test_controller().update(&state, time::Instant::now(), time::Duration::from_millis(10), no_event()).unwrap();
+
+        assert!((state.ball.velocity.magnitude() - 100.0).abs() < 0.01);
+        assert_eq!(state.best_rally, 4);
+    }
+
+    #[test]
+    fn paddle_hit_counts_rally_test() {
+        let mut state = test_state();
+        state.ball.pos = FPoint::new(40.0, 300.0);
+        state.ball.velocity = FPoint::new(-100.0, 0.0);
+
+        let state = test_controller().update(&state, time::Instant::now(), time::Duration::from_millis(10), no_event()).unwrap();
+
+        assert_eq!(state.rally, 1);
+        assert_eq!(state.score, Score::default());
+        assert!(state.ball.velocity.x() > 0.0);
+    }
+
+    #[test]
+    fn replay_is_deterministic_test() {
+        let mut state = test_state();
+        state.ball.physics.restitution_angle_variance = 1.0;
+        state.ball.pos = FPoint::new(40.0, 300.0);
+        state.ball.velocity = FPoint::new(-100.0, 0.0);
+
+        // a copy of the state carries its random numbers along, so both bounce the same way
+        let first = test_controller().update(&state, time::Instant::now(), time::Duration::from_millis(10), no_event()).unwrap();
+        let second = test_controller().update(&state, time::Instant::now(), time::Duration::from_millis(10), no_event()).unwrap();
+        assert_eq!(first.ball.velocity, second.ball.velocity);
+        assert_eq!(first.rng, second.rng);
+
+        let (mut first, mut second) = (state, state);
+        first.serve_ball();
+        second.serve_ball();
+        assert_eq!(first.ball.velocity, second.ball.velocity);
+    }
+
+    #[test]
+    fn paddle_hit_spins_ball_test() {
+        let mut state = test_state();
+        let mut paddle = state.paddle_left;
+        paddle.pos = FPoint::new(20.0, 300.0);
+        paddle.velocity = FPoint::new(0.0, 200.0);
+        state.ball.pos = FPoint::new(35.0, 300.0);
+        state.ball.velocity = FPoint::new(-100.0, 0.0);
+        state.ball.spin = 1.0;
+
+        // the paddle drags the ball along its surface, reversing the spin it had
+        state.ball.collide(&paddle);
+        assert_eq!(state.ball.spin, -1.0 + 200.0 / 10.0);
+
+        state.ball.rotation = 0.0;
+        state.ball.update(time::Duration::from_millis(100));
+        assert!((state.ball.rotation - 1.9).abs() < 0.001);
+    }
+
+    #[test]
+    fn default_ball_sprite_is_cached_test() {
+        let mut theme = Theme::classic();
+        assert!(std::rc::Rc::ptr_eq(&theme.default_ball_sprite(), &theme.default_ball_sprite()));
+        assert!(theme.default_ball_sprite().pixels.contains(&theme.ball));
+        assert_eq!(theme, Theme::classic());
+
+        // changing the colour at runtime rebuilds the sprite
+        theme.ball = Color::RED;
+        assert!(theme.default_ball_sprite().pixels.contains(&Color::RED));
+    }
+
+    #[test]
+    fn trail_ring_buffer_test() {
+        let mut trail = Trail::new();
+        for i in 0..TRAIL_CAPACITY + 5 {
+            trail.push(FPoint::new(i as f32, 0.0));
+        }
+
+        assert_eq!(trail.len(), TRAIL_CAPACITY);
+        let xs: Vec<f32> = trail.iter().map(|point| point.x()).collect();
+        assert_eq!(xs[0], (TRAIL_CAPACITY + 4) as f32);
+        assert_eq!(xs[TRAIL_CAPACITY - 1], 5.0);
+
+        trail.clear();
+        assert_eq!(trail.iter().count(), 0);
+    }
+
+    #[test]
+    fn hit_stop_freezes_ball_test() {
+        let mut state = test_state();
+        state.ball.pos = FPoint::new(40.0, 300.0);
+        state.ball.velocity = FPoint::new(-900.0, 0.0);
+        let mut controller = test_controller();
+
+        let state = controller.update(&state, time::Instant::now(), time::Duration::from_millis(1), no_event()).unwrap();
+        assert!(state.is_hit_stopped());
+
+        let frozen = controller.update(&state, time::Instant::now(), time::Duration::from_millis(1), no_event()).unwrap();
+        assert_eq!(frozen.ball.pos, state.ball.pos);
+
+        // the timer is part of the state, a state without it keeps simulating
+        let mut moving = state;
+        moving.hit_stop_remaining = time::Duration::ZERO;
+        let moving = controller.update(&moving, time::Instant::now(), time::Duration::from_millis(1), no_event()).unwrap();
+        assert_ne!(frozen.ball.pos, moving.ball.pos);
+
+        // switched off, hard hits never freeze the game
+        let mut state = test_state();
+        state.hit_stop = false;
+        state.ball.pos = FPoint::new(40.0, 300.0);
+        state.ball.velocity = FPoint::new(-900.0, 0.0);
+        let state = test_controller().update(&state, time::Instant::now(), time::Duration::from_millis(1), no_event()).unwrap();
+        assert!(!state.is_hit_stopped());
+    }
+
+    #[test]
+    fn logical_screen_test() {
+        let screen = LogicalScreen::new(800, 600);
+
+        // wider window, bars on the sides
+        let pillarbox = screen.letterbox((1000, 600));
+        assert_eq!(pillarbox, sdl2::rect::Rect::new(100, 0, 800, 600));
+        assert_eq!(screen.to_logical(Point::new(100, 0), (1000, 600)), FPoint::new(0.0, 0.0));
+
+        // taller window at half scale, bars on top and bottom
+        let letterbox = screen.letterbox((400, 500));
+        assert_eq!(letterbox, sdl2::rect::Rect::new(0, 100, 400, 300));
+        assert_eq!(screen.to_logical(Point::new(200, 250), (400, 500)), FPoint::new(400.0, 300.0));
+        assert!(screen.to_logical(Point::new(200, 50), (400, 500)).y() < 0.0);
+    }
+}
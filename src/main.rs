@@ -5,33 +5,79 @@
 extern crate rand;
 extern crate sdl2;
 
-use peng::{BallPhysics, GameController, PlayerPaddleController, PangGameController, PangGameState, PaddleAIController};
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use peng::{BallPhysics, BetterPoint, DebugOverlay, LogicalScreen, GameController, GameEvent, PlayerPaddleController, PangGameController, PangGameState, PaddleAIController};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
 use sdl2::libc::can_err_mask_t;
 use sdl2::pixels::Color;
 use sdl2::rect::{FPoint, FRect, Rect};
 use sdl2::render::TextureCreator;
+use sdl2::video::FullscreenType;
 use std::time;
 
-mod tick_controller;
-use tick_controller::tick_controller::TickController;
-
-mod draw_primitives;
-use draw_primitives::draw_primitives::*;
+use peng::tick_controller::{FrameStats, TickController};
+use peng::gradient::{Gradient, GradientTexture};
+use peng::theme::Theme;
+use peng::particles::ParticleSystem;
+use peng::post_process::CrtFilter;
+use peng::screens::ScreenFlow;
+use peng::audio::AudioPlayer;
+use peng::music::{tempo_scale, Song};
+use peng::net::{NetSession, PaddleInput, DEFAULT_PORT};
+use peng::rollback::{RollbackConfig, RollbackSession};
+use peng::spectate::{SpectatorClient, SpectatorServer, DEFAULT_SPECTATE_PORT};
+use peng::breakout::{BreakoutController, BreakoutState, Level};
+use peng::arena::Arena;
+use peng::draw_primitives::draw_primitives::*;
 
 pub fn main() -> Result<(), String> {
     println!("Hello, world!");
     println!("SDL2 Version: {}", sdl2::version::version());
 
+    let mut themes = Theme::builtin();
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--theme") {
+        let path = args.get(index + 1).ok_or("--theme needs a path to a theme file")?;
+        themes.insert(0, Theme::load(std::path::Path::new(path))?);
+    }
+    let mut theme_index = 0;
+
     let window_size = (
         800,
         600,
     );
+    // the physics and drawing happen in this space no matter the window size
+    let screen = LogicalScreen::new(800, 600);
+
+    // has to be set before the video subsystem starts, other platforms use allow_highdpi below
+    sdl2::hint::set("SDL_WINDOWS_DPI_AWARENESS", "permonitorv2");
 
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
 
+    // the game stays playable without a sound device
+    let mut sound = match sdl_context.audio().and_then(|audio| AudioPlayer::open(&audio)) {
+        Ok(sound) => Some(sound),
+        Err(e) => {
+            println!("Sound disabled: {}", e);
+            None
+        }
+    };
+    if let Some(sound) = sound.as_mut() {
+        if args.iter().any(|arg| arg == "--mute") {
+            sound.toggle_mute();
+        }
+        if !args.iter().any(|arg| arg == "--no-music") {
+            match args.iter().position(|arg| arg == "--music") {
+                Some(index) => {
+                    let path = args.get(index + 1).ok_or("--music needs a path to a song file")?;
+                    sound.play_music(Song::load(std::path::Path::new(path))?);
+                }
+                None => sound.play_music(Song::attract()),
+            }
+        }
+    }
+
     let window = video_subsystem
         .window(
             "rust-sdl2 demo: Video",
@@ -40,6 +86,8 @@ pub fn main() -> Result<(), String> {
         )
         .position_centered()
         .opengl()
+        .resizable()
+        .allow_highdpi()
         .build()
         .map_err(|e| e.to_string())?;
 
@@ -50,10 +98,11 @@ pub fn main() -> Result<(), String> {
                                                                     .build().map_err(|e| e.to_string())?;
 
     //canvas.set_blend_mode(sdl2::render::BlendMode::Add);
+    screen.apply(&mut canvas)?;
     canvas.set_draw_color(Color::BLACK);
     canvas.clear();
 
-    let canvas_viewport = canvas.viewport();
+    let playfield = screen.rect();
 
     let use_filtering = true;
     if use_filtering {
@@ -65,63 +114,19 @@ pub fn main() -> Result<(), String> {
 
     let texture_builder = canvas.texture_creator();
 
-    let mut gradient_base_texture = texture_builder.create_texture_streaming(sdl2::pixels::PixelFormatEnum::RGBA32, 2, 1).unwrap();
-    gradient_base_texture.set_blend_mode(sdl2::render::BlendMode::Blend);
-
-    gradient_base_texture.with_lock(None, |buffer: &mut [u8], pitch: usize|{
-        buffer[0] = 255;
-        buffer[1] = 255;
-        buffer[2] = 255;
-        buffer[3] = 255;
-
-        buffer[4] = 0;
-        buffer[5] = 0;
-        buffer[6] = 0;
-        buffer[7] = 0;
-
-        // red to green
-        // buffer[0] = 255;
-        // buffer[1] = 0;
-        // buffer[2] = 0;
-        // buffer[3] = 255;
-
-        // buffer[4] = 0;
-        // buffer[5] = 255;
-        // buffer[6] = 0;
-        // buffer[7] = 255;
-    })?;
-
-    gradient_base_texture.set_color_mod(255, 0, 0);
+    let mut background = GradientTexture::new(&texture_builder, Gradient::two_color(45.0, Color::BLUE, Color::RED));
 
-    let start = time::SystemTime::now();
-
-    loop{
-        canvas.clear();
-
-       let asdf = (time::SystemTime::now().duration_since(start).unwrap().as_secs_f32() / 5.0).fract() as f32;
-       let alpha = ((asdf * 2.0 * std::f32::consts::PI).sin() * 255.0/2.0 + 255.0/2.0 ) as u8;
-
-        gradient_base_texture.set_alpha_mod(alpha);
-
-        canvas.copy(&gradient_base_texture, None, Rect::new(0, 0, canvas_viewport.width()/2, canvas_viewport.height()))?;
-        canvas.copy_ex(
-            &gradient_base_texture, 
-            None, 
-            Rect::new((canvas_viewport.width()/2) as i32, 0, canvas_viewport.width()/2, canvas_viewport.height()), 
-            0.0, 
-            None, 
-            true, 
-            true)?;
-
-        canvas.present();
-
-        break;
-    }
+    let mut crt_filter = CrtFilter::new(&texture_builder);
+    crt_filter.enabled = args.iter().any(|arg| arg == "--crt");
 
     canvas.present();
 
     let mut event_pump = sdl_context.event_pump()?;
 
+    // game controllers drive the menus, they have to stay open to keep sending events
+    let game_controller_subsystem = sdl_context.game_controller().ok();
+    let mut game_controllers: Vec<sdl2::controller::GameController> = Vec::new();
+
     let initial_velocity = 250.0;
     let initial_angle = rand::random::<f32>() * std::f32::consts::PI * 2.0;
 
@@ -132,19 +137,20 @@ pub fn main() -> Result<(), String> {
         restitution_angle_variance: 0.0,
         inherited_velocity: 0.0,
         max_velocity: 1000.0,
+        serve_velocity: initial_velocity,
     };
 
     let ball = peng::Ball {
         size: 50,
-        pos: FPoint::new(
-            canvas_viewport.center().x() as f32,
-            canvas_viewport.center().y() as f32,
-        ),
+        pos: playfield.center(),
         velocity: FPoint::new(
             initial_velocity * initial_angle.cos(),
             initial_velocity * initial_angle.sin(),
         ),
         physics: ball_physics.clone(),
+        rotation: 0.0,
+        spin: 2.0 * std::f32::consts::PI,
+        trail: peng::Trail::new(),
     };
 
     let paddle_offset = 20.0;
@@ -152,7 +158,7 @@ pub fn main() -> Result<(), String> {
 
     let paddle_left = peng::Paddle {
         size: paddle_size,
-        pos: FPoint::new( canvas.viewport().left() as f32 + paddle_offset, canvas.viewport().center().y() as f32),
+        pos: FPoint::new(playfield.left() + paddle_offset, playfield.center().y()),
         velocity: FPoint::new(0.0, 0.0),
         acceleration: FPoint::new(0.0, 0.0),
         movement_speed: 500.0,
@@ -160,7 +166,7 @@ pub fn main() -> Result<(), String> {
 
     let paddle_right = peng::Paddle {
         size: paddle_size,
-        pos: FPoint::new(  canvas.viewport().right() as f32 - paddle_offset, canvas.viewport().center().y() as f32),
+        pos: FPoint::new(playfield.right() - paddle_offset, playfield.center().y()),
         velocity: FPoint::new(0.0, 0.0),
         acceleration: FPoint::new(0.0, 0.0),
         movement_speed: 500.0,
@@ -169,67 +175,391 @@ pub fn main() -> Result<(), String> {
     let target_fps = 600;
     let mut tick_controller = TickController::from_target_fps(target_fps);
 
-    let mut game_state = PangGameState {
-        ball: ball,
-        paddle_left: paddle_left,
-        paddle_right: paddle_right,
-        canvas: FRect::new(
-            canvas_viewport.x() as f32,
-            canvas_viewport.y() as f32, 
-            canvas_viewport.width() as f32, 
-            canvas_viewport.height() as f32,
-        ),
-    };
+    // --breakout plays the brick game instead, on the given level or the built-in ones in a row
+    if let Some(index) = args.iter().position(|arg| arg == "--breakout") {
+        let levels = match args.get(index + 1).filter(|arg| !arg.starts_with("--")) {
+            Some(path) => vec![Level::load(std::path::Path::new(path))?],
+            None => Level::builtin(),
+        };
+        let mut breakout = BreakoutController::new(levels)?;
+        breakout.theme = themes[theme_index].clone();
+        if !args.iter().any(|arg| arg == "--no-particles") {
+            breakout.particles = Some(ParticleSystem::new(2048));
+        }
+
+        // a smaller ball that speeds up slower than in pong, the bricks keep it busy
+        let mut ball = ball;
+        ball.size = 16;
+        ball.physics.restitution_factor = 1.02;
+        ball.physics.serve_velocity = 350.0;
+        ball.physics.max_velocity = 800.0;
+        let state = breakout.new_game(ball, paddle_left, playfield);
+        return run_breakout(&mut canvas, &mut event_pump, &screen, breakout, state, &mut sound, &mut crt_filter);
+    }
+
+    let mut game_state = PangGameState::new(
+        ball,
+        paddle_left,
+        paddle_right,
+        playfield,
+    );
+    game_state.hit_stop = !args.iter().any(|arg| arg == "--no-hit-stop");
+
+    let mut game_state_controller = PangGameController::new(
+        Box::new(PaddleAIController::new()),
+        //Box::new(PlayerPaddleController::new(Keycode::W, Keycode::S)),
+        Box::new(PaddleAIController::new()),
+    );
+    game_state_controller.theme = themes[theme_index].clone();
+    if !args.iter().any(|arg| arg == "--no-particles") {
+        game_state_controller.particles = Some(ParticleSystem::new(2048));
+    }
+    game_state_controller.effects.screen_shake = !args.iter().any(|arg| arg == "--no-screen-shake");
+    if let Some(index) = args.iter().position(|arg| arg == "--arena") {
+        let path = args.get(index + 1).ok_or("--arena needs a path to an arena file")?;
+        game_state_controller.arena = Some(Arena::load(std::path::Path::new(path))?);
+    }
 
-    let mut game_state_controller = PangGameController{
-        paddle_controller_left: Box::new(PaddleAIController::new()),
-        //paddle_controller_left: PlayerPaddleController::new(Keycode::W, Keycode::S),
-        paddle_controller_right: Box::new(PaddleAIController::new()),
+    let mut flow = ScreenFlow::new(game_state, game_state_controller);
+
+    // --host and --join alone play host-authoritative, with --rollback both sides simulate
+    let use_rollback = args.iter().any(|arg| arg == "--rollback");
+    let mut rollback_config = RollbackConfig::default();
+    if let Some(index) = args.iter().position(|arg| arg == "--input-delay") {
+        rollback_config.input_delay = args.get(index + 1).and_then(|ticks| ticks.parse().ok()).ok_or("--input-delay needs a number of ticks")?;
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--rollback-window") {
+        rollback_config.rollback_window = args.get(index + 1).and_then(|ticks| ticks.parse().ok()).ok_or("--rollback-window needs a number of ticks")?;
+    }
+
+    let mut net_session = None;
+    let mut rollback_session = None;
+    if let Some(index) = args.iter().position(|arg| arg == "--host") {
+        let port = match args.get(index + 1).filter(|arg| !arg.starts_with("--")) {
+            Some(port) => port.parse::<u16>().map_err(|_| format!("--host: invalid port {}", port))?,
+            None => DEFAULT_PORT,
+        };
+        println!("Hosting on port {}", port);
+        if use_rollback {
+            rollback_session = Some(RollbackSession::host(("0.0.0.0", port), &mut flow, rollback_config)?);
+        } else {
+            net_session = Some(NetSession::host(("0.0.0.0", port), &mut flow)?);
+        }
+    } else if let Some(index) = args.iter().position(|arg| arg == "--join") {
+        let address = args.get(index + 1).ok_or("--join needs the host address")?;
+        let address = if address.contains(':') { address.clone() } else { format!("{}:{}", address, DEFAULT_PORT) };
+        println!("Joining {}", address);
+        if use_rollback {
+            rollback_session = Some(RollbackSession::join(address.as_str(), &mut flow, rollback_config)?);
+        } else {
+            net_session = Some(NetSession::join(address.as_str(), &mut flow)?);
+        }
+    }
+
+    // --spectate streams whatever is played here, --watch shows such a stream instead of playing
+    let mut spectator_server = match args.iter().position(|arg| arg == "--spectate") {
+        Some(index) => {
+            let port = match args.get(index + 1).filter(|arg| !arg.starts_with("--")) {
+                Some(port) => port.parse::<u16>().map_err(|_| format!("--spectate: invalid port {}", port))?,
+                None => DEFAULT_SPECTATE_PORT,
+            };
+            println!("Streaming to spectators on port {}", port);
+            Some(SpectatorServer::bind(("0.0.0.0", port))?)
+        }
+        None => None,
+    };
+    let mut spectator_client = match args.iter().position(|arg| arg == "--watch") {
+        Some(index) => {
+            let address = args.get(index + 1).ok_or("--watch needs the address of the stream")?;
+            let address = if address.contains(':') { address.clone() } else { format!("{}:{}", address, DEFAULT_SPECTATE_PORT) };
+            println!("Watching {}", address);
+            Some(SpectatorClient::connect(address.as_str(), &mut flow)?)
+        }
+        None => None,
     };
 
+    let mut debug_overlay = DebugOverlay::new();
+    let mut paused_by_window = false;
+
     'running: loop {
-        game_state = game_state_controller.update(&game_state, time::Instant::now(), tick_controller.elapsed_since_last_tick(), Event::Unknown { timestamp: 0, type_: 0 })?;
+        let game_delta = tick_controller.game_delta();
+        flow.update(game_delta)?;
 
         for event in event_pump.poll_iter(){
+            if handle_shell_event(&event, &mut canvas, &mut tick_controller, &mut paused_by_window, &mut crt_filter, &mut sound)? {
+                continue;
+            }
             match &event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => break 'running,
+                Event::Quit { .. } => break 'running,
+                Event::ControllerDeviceAdded {which, ..} => {
+                    if let Some(subsystem) = &game_controller_subsystem {
+                        match subsystem.open(*which) {
+                            Ok(game_controller) => {
+                                println!("Game controller connected: {}", game_controller.name());
+                                game_controllers.push(game_controller);
+                            }
+                            Err(e) => println!("Could not open game controller {}: {}", which, e),
+                        }
+                    }
+                },
+                Event::ControllerDeviceRemoved {which, ..} => {
+                    game_controllers.retain(|game_controller| game_controller.instance_id() != *which);
+                },
                 Event::KeyDown {keycode: Some(Keycode::R), ..} => {
-                    game_state.ball.pos = FPoint::new(
-                        canvas.viewport().center().x() as f32,
-                        canvas.viewport().center().y() as f32,
-                    );
-                    game_state.ball.velocity = FPoint::new(
-                        initial_velocity * initial_angle.cos(),
-                        initial_velocity * initial_angle.sin(),
-                    );
+                    flow.state.serve_ball();
+                },
+                Event::KeyDown {keycode: Some(Keycode::T), ..} => {
+                    theme_index = (theme_index + 1) % themes.len();
+                    flow.controller.theme = themes[theme_index].clone();
+                    println!("Theme: {}", flow.controller.theme.name);
+                },
+                Event::KeyDown {keycode: Some(Keycode::B), ..} => {
+                    let theme = &mut flow.controller.theme;
+                    theme.ball_shape = theme.ball_shape.next();
                 },
-                _ => {game_state_controller.update(&mut game_state, time::Instant::now(), time::Duration::from_micros(0), event)?;}
+                Event::KeyDown {keycode: Some(Keycode::L), ..} => {
+                    let theme = &mut flow.controller.theme;
+                    theme.ball_trail = !theme.ball_trail;
+                },
+                Event::KeyDown {keycode: Some(Keycode::F3), ..} => {
+                    debug_overlay.toggle();
+                },
+                _ => flow.handle_event(&event)?,
             }
         }
+        if flow.wants_quit() {
+            break 'running;
+        }
 
-        canvas.set_draw_color(sdl2::pixels::Color::BLACK);
-        canvas.clear();
-
-        let rect = Rect::from_center(canvas_viewport.center(), 400, 300 );
-        draw_gradient_rect(&mut canvas, &mut gradient_base_texture, rect, 45.0, sdl2::pixels::Color::BLUE, sdl2::pixels::Color::RED)?;
-        game_state_controller.draw(&game_state, &mut canvas)?;
+        if let Some(session) = net_session.as_mut() {
+            session.update(&mut flow, PaddleInput::from_keyboard(&event_pump.keyboard_state()));
+            if session.is_closed() && flow.network.is_none() {
+                net_session = None;
+            }
+        }
+        if let Some(session) = rollback_session.as_mut() {
+            session.update(&mut flow, PaddleInput::from_keyboard(&event_pump.keyboard_state()), game_delta)?;
+            if session.is_closed() && flow.network.is_none() {
+                rollback_session = None;
+            }
+        }
+        if let Some(client) = spectator_client.as_mut() {
+            client.update(&mut flow);
+            if client.is_closed() && flow.network.is_none() {
+                spectator_client = None;
+            }
+        }
+        if let Some(server) = spectator_server.as_mut() {
+            server.broadcast(&flow);
+        }
 
+        // mouse state is in window coordinates, unlike mouse events SDL does not map it for us
+        let mouse = event_pump.mouse_state();
+        debug_overlay.cursor = Some(screen.to_logical(sdl2::rect::Point::new(mouse.x(), mouse.y()), canvas.window().size()));
 
-        canvas.present();
+        let stats = tick_controller.stats();
+        present_frame(&mut canvas, &mut crt_filter, &mut background, &screen, &flow.controller.theme, &stats, |canvas| {
+            flow.draw(canvas)?;
+            debug_overlay.draw(&flow.state, &flow.controller, &stats, canvas)
+        })?;
 
-        game_state.canvas = FRect::new(
-            canvas.viewport().x() as f32,
-            canvas.viewport().y() as f32, 
-            canvas.viewport().width() as f32, 
-            canvas.viewport().height() as f32,
-        );
+        if let Some(sound) = sound.as_mut() {
+            let speed_ratio = flow.state.ball.velocity.magnitude() / flow.state.ball.physics.max_velocity;
+            sound.set_music_tempo(tempo_scale(flow.state.rally, speed_ratio));
+        }
+        play_events(&mut sound, flow.controller.take_events(), flow.state.ball.physics.max_velocity);
 
         tick_controller.wait_for_next_tick();
     }
 
     return Ok(());
 }
+
+// the brick game shares the window, sound and filters with pong but runs its own loop, escape quits
+fn run_breakout(
+    canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
+    event_pump: &mut sdl2::EventPump,
+    screen: &LogicalScreen,
+    mut controller: BreakoutController,
+    mut state: BreakoutState,
+    sound: &mut Option<AudioPlayer>,
+    crt_filter: &mut CrtFilter<sdl2::video::WindowContext>,
+) -> Result<(), String> {
+    let mut tick_controller = TickController::from_target_fps(600);
+    let texture_builder = canvas.texture_creator();
+    let mut background = GradientTexture::new(&texture_builder, Gradient::two_color(45.0, Color::BLUE, Color::RED));
+    let mut paused_by_window = false;
+
+    'running: loop {
+        let game_delta = tick_controller.game_delta();
+        state = controller.update(&state, time::Instant::now(), game_delta, Event::Unknown { timestamp: 0, type_: 0 })?;
+
+        for event in event_pump.poll_iter() {
+            if handle_shell_event(&event, canvas, &mut tick_controller, &mut paused_by_window, crt_filter, sound)? {
+                continue;
+            }
+            match &event {
+                Event::Quit { .. } | Event::KeyDown {keycode: Some(Keycode::Escape), ..} => break 'running,
+                _ => state = controller.update(&state, time::Instant::now(), time::Duration::ZERO, event.clone())?,
+            }
+        }
+
+        let stats = tick_controller.stats();
+        present_frame(canvas, crt_filter, &mut background, screen, &controller.theme, &stats, |canvas| controller.draw(&state, canvas))?;
+        play_events(sound, controller.take_events(), state.ball.physics.max_velocity);
+
+        tick_controller.wait_for_next_tick();
+    }
+
+    Ok(())
+}
+
+// window, time control, filter and sound keys that work the same in every game, returns true
+// when `event` was one of them
+fn handle_shell_event(
+    event: &Event,
+    canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
+    tick_controller: &mut TickController,
+    // set when the pause came from the window losing focus, so refocusing does not undo a manual pause
+    paused_by_window: &mut bool,
+    crt_filter: &mut CrtFilter<sdl2::video::WindowContext>,
+    sound: &mut Option<AudioPlayer>,
+) -> Result<bool, String> {
+    match event {
+        Event::KeyDown {keycode: Some(Keycode::F11), ..} => {
+            toggle_fullscreen(canvas)?;
+        },
+        Event::KeyDown {keycode: Some(Keycode::Return), keymod, ..} if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => {
+            toggle_fullscreen(canvas)?;
+        },
+        Event::Window {win_event: WindowEvent::FocusLost, ..}
+        | Event::Window {win_event: WindowEvent::Minimized, ..} => {
+            if !tick_controller.is_paused() {
+                tick_controller.pause();
+                *paused_by_window = true;
+            }
+        },
+        Event::Window {win_event: WindowEvent::FocusGained, ..}
+        | Event::Window {win_event: WindowEvent::Restored, ..} => {
+            if *paused_by_window {
+                tick_controller.resume();
+                *paused_by_window = false;
+            }
+        },
+        Event::Window {win_event: WindowEvent::SizeChanged(..), ..} => {
+            let (width, height) = canvas.output_size()?;
+            println!("Window resized, drawing at {}x{} pixels", width, height);
+        },
+        Event::KeyDown {keycode: Some(Keycode::P), ..} => {
+            tick_controller.toggle_pause();
+            *paused_by_window = false;
+        },
+        Event::KeyDown {keycode: Some(Keycode::Period), ..} => {
+            tick_controller.step();
+        },
+        Event::KeyDown {keycode: Some(Keycode::LeftBracket), ..} => {
+            tick_controller.slower();
+        },
+        Event::KeyDown {keycode: Some(Keycode::RightBracket), ..} => {
+            tick_controller.faster();
+        },
+        Event::KeyDown {keycode: Some(Keycode::Backslash), ..} => {
+            tick_controller.set_time_scale(1.0);
+        },
+        Event::KeyDown {keycode: Some(Keycode::C), ..} => {
+            crt_filter.toggle();
+        },
+        Event::KeyDown {keycode: Some(Keycode::M), ..} => {
+            if let Some(sound) = sound.as_mut() {
+                sound.toggle_mute();
+            }
+        },
+        Event::KeyDown {keycode: Some(Keycode::N), ..} => {
+            if let Some(sound) = sound.as_mut() {
+                if sound.is_music_playing() {
+                    sound.stop_music();
+                } else {
+                    sound.play_music(Song::attract());
+                }
+            }
+        },
+        Event::KeyDown {keycode: Some(Keycode::Minus), ..} => {
+            if let Some(sound) = sound.as_mut() {
+                let volume = sound.volume() - 0.1;
+                sound.set_volume(volume);
+            }
+        },
+        Event::KeyDown {keycode: Some(Keycode::Equals), ..} => {
+            if let Some(sound) = sound.as_mut() {
+                let volume = sound.volume() + 0.1;
+                sound.set_volume(volume);
+            }
+        },
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+// one frame through the CRT filter: the theme background, the game drawn by `draw` and the time
+// control on top
+fn present_frame<F>(
+    canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
+    crt_filter: &mut CrtFilter<sdl2::video::WindowContext>,
+    background: &mut GradientTexture<sdl2::video::WindowContext>,
+    screen: &LogicalScreen,
+    theme: &Theme,
+    stats: &FrameStats,
+    draw: F,
+) -> Result<(), String>
+where
+    F: FnOnce(&mut sdl2::render::Canvas<sdl2::video::Window>) -> Result<(), String>,
+{
+    crt_filter.render(canvas, |canvas| {
+        canvas.set_draw_color(theme.background);
+        canvas.clear();
+
+        if let Some(gradient) = &theme.background_gradient {
+            background.set_gradient(gradient.clone());
+            background.draw(canvas, Rect::new(0, 0, screen.width, screen.height))?;
+        }
+        draw(canvas)?;
+        draw_time_control(canvas, screen, stats, theme.hud)
+    })?;
+
+    canvas.present();
+    Ok(())
+}
+
+fn play_events(sound: &mut Option<AudioPlayer>, events: Vec<GameEvent>, max_velocity: f32) {
+    if let Some(sound) = sound.as_mut() {
+        for event in events {
+            sound.play_event(&event, max_velocity);
+        }
+    }
+}
+
+// pause banner in the middle of the field, the time scale in the corner when it is not 1x
+fn draw_time_control<T: sdl2::render::RenderTarget>(canvas: &mut sdl2::render::Canvas<T>, screen: &LogicalScreen, stats: &FrameStats, color: Color) -> Result<(), String> {
+    let field = screen.rect();
+    if stats.paused {
+        let scale = 4.0;
+        let (_, text_height) = text_size("PAUSED", scale);
+        draw_text(canvas, "PAUSED", FPoint::new(field.center().x(), field.center().y() - text_height), scale, TextAlign::Center, color)?;
+        draw_text(canvas, "P RESUME  . STEP", FPoint::new(field.center().x(), field.center().y() + text_height / 2.0), 2.0, TextAlign::Center, color)?;
+    }
+    if stats.time_scale != 1.0 {
+        let label = format!("{}X", stats.time_scale);
+        draw_text(canvas, &label, FPoint::new(field.right() - 20.0, field.top() + 20.0), 3.0, TextAlign::Right, color)?;
+    }
+    Ok(())
+}
+
+// borderless fullscreen at the desktop resolution, the logical size keeps the playfield intact
+fn toggle_fullscreen(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) -> Result<(), String> {
+    let window = canvas.window_mut();
+    let fullscreen = match window.fullscreen_state() {
+        FullscreenType::Off => FullscreenType::Desktop,
+        _ => FullscreenType::Off,
+    };
+    window.set_fullscreen(fullscreen)
+}
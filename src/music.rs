@@ -0,0 +1,360 @@
+#![allow(dead_code)]
+
+use crate::theme::{key_value_lines, line_error};
+
+// every row is a sixteenth note
+const ROWS_PER_BEAT: f32 = 4.0;
+// pitch of the noise channel's `x` hits
+const NOISE_FREQUENCY: f32 = 8000.0;
+// noise hits are short, tonal notes ring until the next one
+const NOISE_DECAY: f32 = 0.12;
+const MAX_TEMPO_SCALE: f32 = 1.75;
+
+pub const CHANNEL_NAMES: [&str; 3] = ["square", "triangle", "noise"];
+const SQUARE: usize = 0;
+const TRIANGLE: usize = 1;
+const NOISE: usize = 2;
+// mix levels, the square is the loudest waveform for the same amplitude
+const CHANNEL_VOLUME: [f32; 3] = [0.3, 0.5, 0.25];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Step {
+    Note(f32),
+    Hold,
+    Rest,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pattern {
+    pub name: String,
+    // one step list per channel, all the same length
+    pub channels: [Vec<Step>; 3],
+}
+
+impl Pattern {
+    pub fn rows(&self) -> usize {
+        self.channels[SQUARE].len()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Song {
+    // beats per minute
+    pub tempo: f32,
+    pub patterns: Vec<Pattern>,
+    // indices into patterns
+    pub order: Vec<usize>,
+}
+
+impl Song {
+    /// The loop bundled with the game for attract mode.
+    pub fn attract() -> Song {
+        Song::parse(include_str!("../music/attract.song")).expect("bundled song is valid")
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Song, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Song::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parses a song from `key = value` lines, comments work like in theme files. `pattern = <name>`
+    /// starts a pattern, the channel keys that follow fill it with whitespace separated steps and
+    /// `order` lists the pattern names to play. Without `order` the patterns play as declared.
+    pub fn parse(text: &str) -> Result<Song, String> {
+        let mut song = Song {
+            tempo: 120.0,
+            patterns: Vec::new(),
+            order: Vec::new(),
+        };
+        let mut order: Option<(usize, Vec<String>)> = None;
+
+        for line in key_value_lines(text) {
+            let (line, key, value) = line?;
+            let error = |message: String| line_error(line, message);
+
+            match key {
+                "tempo" => {
+                    song.tempo = value.parse::<f32>().ok().filter(|tempo| *tempo > 0.0)
+                        .ok_or_else(|| error(format!("invalid tempo `{}`", value)))?;
+                }
+                "pattern" => {
+                    if song.patterns.iter().any(|pattern| pattern.name == value) {
+                        return Err(error(format!("pattern `{}` is defined twice", value)));
+                    }
+                    song.patterns.push(Pattern {
+                        name: value.to_string(),
+                        channels: [Vec::new(), Vec::new(), Vec::new()],
+                    });
+                }
+                "order" => order = Some((line, value.split_whitespace().map(String::from).collect())),
+                _ => {
+                    let channel = CHANNEL_NAMES.iter().position(|name| *name == key)
+                        .ok_or_else(|| error(format!("unknown key `{}`", key)))?;
+                    let pattern = song.patterns.last_mut()
+                        .ok_or_else(|| error(format!("`{}` needs a `pattern` line before it", key)))?;
+                    pattern.channels[channel] = value.split_whitespace()
+                        .map(|token| parse_step(token, channel == NOISE))
+                        .collect::<Result<Vec<Step>, String>>()
+                        .map_err(error)?;
+                }
+            }
+        }
+
+        for pattern in &song.patterns {
+            let rows = pattern.channels.iter().map(|steps| steps.len()).max().unwrap_or(0);
+            for (channel, steps) in pattern.channels.iter().enumerate() {
+                if steps.len() != rows {
+                    return Err(format!("pattern `{}`: {} has {} rows, expected {}", pattern.name, CHANNEL_NAMES[channel], steps.len(), rows));
+                }
+            }
+            if rows == 0 {
+                return Err(format!("pattern `{}` is empty", pattern.name));
+            }
+        }
+
+        song.order = match order {
+            Some((line, names)) => names.iter()
+                .map(|name| song.patterns.iter().position(|pattern| pattern.name == *name)
+                    .ok_or_else(|| line_error(line, format!("unknown pattern `{}`", name))))
+                .collect::<Result<Vec<usize>, String>>()?,
+            None => (0..song.patterns.len()).collect(),
+        };
+        if song.order.is_empty() {
+            return Err(String::from("song has no patterns"));
+        }
+        Ok(song)
+    }
+}
+
+fn parse_step(token: &str, noise: bool) -> Result<Step, String> {
+    match token {
+        "---" => Ok(Step::Hold),
+        "..." | "." => Ok(Step::Rest),
+        "x" if noise => Ok(Step::Note(NOISE_FREQUENCY)),
+        _ => note_frequency(token).map(Step::Note).ok_or_else(|| format!("invalid step `{}`", token)),
+    }
+}
+
+/// Frequency of a tracker style note like `A-4` or `C#5`, `A-4` is 440 Hz.
+pub fn note_frequency(note: &str) -> Option<f32> {
+    let mut chars = note.chars();
+    let semitone = match chars.next()? {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let semitone = match chars.next()? {
+        '-' => semitone,
+        '#' => semitone + 1,
+        _ => return None,
+    };
+    let octave = chars.as_str().parse::<i32>().ok().filter(|octave| (0..=9).contains(octave))?;
+    let midi_note = (octave + 1) * 12 + semitone;
+    Some(440.0 * 2f32.powf((midi_note - 69) as f32 / 12.0))
+}
+
+/// How much faster the music plays for a rally of `rally` hits with the ball at
+/// `speed_ratio` of its max_velocity.
+pub fn tempo_scale(rally: u32, speed_ratio: f32) -> f32 {
+    (1.0 + rally as f32 * 0.02 + speed_ratio.clamp(0.0, 1.0) * 0.3).min(MAX_TEMPO_SCALE)
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Channel {
+    frequency: f32,
+    playing: bool,
+    // 0 to 1, position within the current period
+    phase: f32,
+    // seconds since the note started
+    age: f32,
+}
+
+/// Plays a `Song` in a loop one sample at a time, mixed into the audio callback by the synth.
+pub struct Sequencer {
+    song: Song,
+    sample_rate: f32,
+    pub tempo_scale: f32,
+    order_index: usize,
+    row: usize,
+    samples_to_next_row: f32,
+    channels: [Channel; 3],
+    noise_register: u16,
+}
+
+impl Sequencer {
+    pub fn new(song: Song, sample_rate: i32) -> Sequencer {
+        let silent = Channel {
+            frequency: 0.0,
+            playing: false,
+            phase: 0.0,
+            age: 0.0,
+        };
+        Sequencer {
+            song,
+            sample_rate: sample_rate as f32,
+            tempo_scale: 1.0,
+            order_index: 0,
+            row: 0,
+            samples_to_next_row: 0.0,
+            channels: [silent; 3],
+            noise_register: 1,
+        }
+    }
+
+    pub fn song(&self) -> &Song {
+        &self.song
+    }
+
+    // (position in the order list, row within the pattern) of the next row to play
+    pub fn position(&self) -> (usize, usize) {
+        (self.order_index, self.row)
+    }
+
+    fn play_row(&mut self) {
+        let pattern = &self.song.patterns[self.song.order[self.order_index]];
+        for (channel, steps) in self.channels.iter_mut().zip(pattern.channels.iter()) {
+            match steps[self.row] {
+                Step::Note(frequency) => {
+                    channel.frequency = frequency;
+                    channel.playing = true;
+                    channel.age = 0.0;
+                }
+                Step::Hold => {}
+                Step::Rest => channel.playing = false,
+            }
+        }
+
+        self.row += 1;
+        if self.row == pattern.rows() {
+            self.row = 0;
+            self.order_index = (self.order_index + 1) % self.song.order.len();
+        }
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        if self.samples_to_next_row <= 0.0 {
+            self.play_row();
+            let rows_per_second = self.song.tempo / 60.0 * ROWS_PER_BEAT * self.tempo_scale.max(0.01);
+            self.samples_to_next_row += self.sample_rate / rows_per_second;
+        }
+        self.samples_to_next_row -= 1.0;
+
+        let mut mixed = 0.0;
+        for (index, channel) in self.channels.iter_mut().enumerate() {
+            if !channel.playing {
+                continue;
+            }
+
+            let mut clocked = false;
+            channel.phase += channel.frequency / self.sample_rate;
+            if channel.phase >= 1.0 {
+                channel.phase = channel.phase.fract();
+                clocked = true;
+            }
+            channel.age += 1.0 / self.sample_rate;
+
+            let sample = match index {
+                SQUARE => if channel.phase < 0.5 { 1.0 } else { -1.0 },
+                TRIANGLE => 4.0 * (channel.phase - 0.5).abs() - 1.0,
+                _ => {
+                    // 15 bit linear feedback shift register, stepped once per period
+                    if clocked {
+                        let bit = (self.noise_register ^ (self.noise_register >> 1)) & 1;
+                        self.noise_register = (self.noise_register >> 1) | (bit << 14);
+                    }
+                    let envelope = (1.0 - channel.age / NOISE_DECAY).max(0.0);
+                    if self.noise_register & 1 == 1 { envelope } else { -envelope }
+                }
+            };
+            mixed += sample * CHANNEL_VOLUME[index];
+        }
+        mixed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::music::{note_frequency, tempo_scale, Sequencer, Song, Step};
+
+    #[test]
+    fn note_frequency_test() {
+        assert_eq!(note_frequency("A-4"), Some(440.0));
+        assert!((note_frequency("C-4").unwrap() - 261.63).abs() < 0.01);
+        assert!((note_frequency("A#4").unwrap() - 466.16).abs() < 0.01);
+        assert_eq!(note_frequency("H-4"), None);
+        assert_eq!(note_frequency("A4"), None);
+    }
+
+    #[test]
+    fn parse_song_test() {
+        let song = Song::parse("
+            tempo = 150
+            pattern = intro  # comment
+            square   = C-4 --- ...
+            triangle = C-2 ... ...
+            noise    = x   ... x
+            pattern = verse
+            square   = A-4
+            triangle = ...
+            noise    = ...
+            order = verse intro verse
+        ").unwrap();
+
+        assert_eq!(song.tempo, 150.0);
+        assert_eq!(song.patterns.len(), 2);
+        assert_eq!(song.patterns[0].rows(), 3);
+        assert_eq!(song.patterns[0].channels[0][1], Step::Hold);
+        assert_eq!(song.patterns[0].channels[1][1], Step::Rest);
+        assert_eq!(song.patterns[1].channels[0][0], Step::Note(440.0));
+        assert_eq!(song.order, vec![1, 0, 1]);
+
+        assert!(Song::parse("square = C-4").unwrap_err().contains("line 1"));
+        assert!(Song::parse("pattern = a\nsquare = C-4 C-4\ntriangle = C-4\nnoise = x x").unwrap_err().contains("triangle"));
+        assert!(Song::parse("pattern = a\nsquare = Q-4\ntriangle = .\nnoise = .").unwrap_err().contains("line 2"));
+        assert!(Song::parse("pattern = a\nsquare = x\ntriangle = .\nnoise = .").is_err());
+        assert!(Song::parse("pattern = a\nsquare = .\ntriangle = .\nnoise = .\norder = a b").unwrap_err().contains("`b`"));
+
+        // the bundled loop has to parse
+        Song::attract();
+    }
+
+    #[test]
+    fn sequencer_loops_test() {
+        let song = Song::parse("
+            tempo = 60
+            pattern = a
+            square   = A-4 ...
+            triangle = ... ...
+            noise    = ... ...
+        ").unwrap();
+        // at 60 bpm and 4 rows per beat a row is 1/4 second, 100 samples at 400 Hz
+        let mut sequencer = Sequencer::new(song, 400);
+
+        let first_row: Vec<f32> = (0..100).map(|_| sequencer.next_sample()).collect();
+        assert!(first_row.iter().any(|sample| *sample != 0.0));
+        assert_eq!(sequencer.position(), (0, 1));
+
+        let second_row: Vec<f32> = (0..100).map(|_| sequencer.next_sample()).collect();
+        assert!(second_row.iter().all(|sample| *sample == 0.0));
+
+        // back at the start, twice as fast
+        sequencer.tempo_scale = 2.0;
+        sequencer.next_sample();
+        assert_eq!(sequencer.position(), (0, 1));
+        (0..50).for_each(|_| { sequencer.next_sample(); });
+        assert_eq!(sequencer.position(), (0, 0));
+    }
+
+    #[test]
+    fn tempo_rises_test() {
+        assert_eq!(tempo_scale(0, 0.0), 1.0);
+        assert!(tempo_scale(10, 0.0) > tempo_scale(2, 0.0));
+        assert!(tempo_scale(2, 0.8) > tempo_scale(2, 0.2));
+        assert!(tempo_scale(1000, 1.0) <= 1.75);
+    }
+}
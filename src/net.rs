@@ -0,0 +1,957 @@
+#![allow(dead_code)]
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use sdl2::event::Event;
+use sdl2::keyboard::{KeyboardState, Keycode, Scancode};
+use sdl2::rect::{FPoint, FRect};
+
+use crate::arena::{Arena, Obstacle, Portal, Shape};
+use crate::screens::{NetworkRole, Screen, ScreenFlow};
+use crate::{BallPhysics, GameRng, Paddle, PaddleController, PangGameState, PlayerPaddleController, Score, Side};
+
+pub const DEFAULT_PORT: u16 = 7777;
+const MAGIC: &[u8; 4] = b"PENG";
+const PROTOCOL_VERSION: u8 = 7;
+// the peer is dropped after this long without a packet
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+const HELLO_INTERVAL: Duration = Duration::from_millis(250);
+// inputs and snapshots go out at most this often, the game loop ticks much faster
+const SEND_INTERVAL: Duration = Duration::from_micros(16_667);
+// the welcome carries the arena, this leaves room for a few dozen obstacles and stays below
+// the usual MTU
+const MAX_PACKET_SIZE: usize = 1200;
+
+/// What a player holds down, the only thing a client sends.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct PaddleInput {
+    pub up: bool,
+    pub down: bool,
+}
+
+impl PaddleInput {
+    // W/S or the arrow keys
+    pub fn from_keyboard(keyboard: &KeyboardState) -> PaddleInput {
+        PaddleInput {
+            up: keyboard.is_scancode_pressed(Scancode::W) || keyboard.is_scancode_pressed(Scancode::Up),
+            down: keyboard.is_scancode_pressed(Scancode::S) || keyboard.is_scancode_pressed(Scancode::Down),
+        }
+    }
+}
+
+/// Moves a paddle with the input last received from the network.
+pub struct NetworkPaddleController {
+    input: Rc<Cell<PaddleInput>>,
+}
+
+impl NetworkPaddleController {
+    // returns the controller and the handle new inputs are written to
+    pub fn new() -> (NetworkPaddleController, Rc<Cell<PaddleInput>>) {
+        let input = Rc::new(Cell::new(PaddleInput::default()));
+        (NetworkPaddleController { input: input.clone() }, input)
+    }
+}
+
+impl PaddleController for NetworkPaddleController {
+    fn update_paddle(&mut self, _game_state: &PangGameState, _event: &Event, paddle: &mut Paddle) {
+        let input = self.input.get();
+        paddle.velocity = if input.up == input.down {
+            FPoint::new(0.0, 0.0)
+        } else if input.down {
+            paddle.axis() * paddle.movement_speed
+        } else {
+            paddle.axis() * -paddle.movement_speed
+        };
+    }
+}
+
+/// The part of the host's state a client needs to draw the match.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Snapshot {
+    pub tick: u32,
+    pub ball_pos: FPoint,
+    pub ball_velocity: FPoint,
+    pub ball_rotation: f32,
+    pub paddle_left: FPoint,
+    pub paddle_right: FPoint,
+    pub paddle_top: Option<FPoint>,
+    pub paddle_bottom: Option<FPoint>,
+    // left or right walled off for practice
+    pub wall: Option<Side>,
+    pub score: Score,
+    pub rally: u32,
+    pub best_rally: u32,
+    pub match_time: Duration,
+    pub screen: Screen,
+}
+
+impl Snapshot {
+    pub fn capture(tick: u32, state: &PangGameState, screen: Screen) -> Snapshot {
+        Snapshot {
+            tick,
+            ball_pos: state.ball.pos,
+            ball_velocity: state.ball.velocity,
+            ball_rotation: state.ball.rotation,
+            paddle_left: state.paddle_left.pos,
+            paddle_right: state.paddle_right.pos,
+            paddle_top: state.paddle_top.map(|paddle| paddle.pos),
+            paddle_bottom: state.paddle_bottom.map(|paddle| paddle.pos),
+            wall: state.wall,
+            score: state.score,
+            rally: state.rally,
+            best_rally: state.best_rally,
+            match_time: state.match_time,
+            screen,
+        }
+    }
+
+    pub fn apply(&self, state: &mut PangGameState) {
+        state.ball.pos = self.ball_pos;
+        state.ball.velocity = self.ball_velocity;
+        state.ball.rotation = self.ball_rotation;
+        state.ball.trail.push(self.ball_pos);
+        state.paddle_left.pos = self.paddle_left;
+        state.paddle_right.pos = self.paddle_right;
+        for (side, pos) in [(Side::Top, self.paddle_top), (Side::Bottom, self.paddle_bottom)] {
+            match pos {
+                Some(pos) => {
+                    if state.paddle(side).is_none() {
+                        state.add_paddle(side);
+                    }
+                    if let Some(paddle) = state.paddle_mut(side) {
+                        paddle.pos = pos;
+                    }
+                }
+                None => state.remove_paddle(side),
+            }
+        }
+        state.wall = self.wall;
+        state.score = self.score;
+        state.rally = self.rally;
+        state.best_rally = self.best_rally;
+        state.match_time = self.match_time;
+    }
+
+    /// Applies the snapshot to the state and screen of a flow that only shows it.
+    pub fn show(self, flow: &mut ScreenFlow) {
+        self.apply(&mut flow.state);
+        flow.screen = self.screen;
+    }
+}
+
+/// The host's geometry, rules and arena. Options are local to every instance, the host sends
+/// its own once in the welcome and clients, rollback peers and spectators take them over.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MatchSetup {
+    pub ball_size: i32,
+    pub physics: BallPhysics,
+    // width and length of the left and right paddles, top and bottom ones are turned
+    pub paddle_size: FPoint,
+    pub paddle_speed: f32,
+    pub points_to_win: u32,
+    pub speed_ramp: bool,
+    pub hit_stop: bool,
+    pub arena: Option<Arena>,
+}
+
+impl MatchSetup {
+    pub fn capture(flow: &ScreenFlow) -> MatchSetup {
+        let state = &flow.state;
+        MatchSetup {
+            ball_size: state.ball.size,
+            physics: state.ball.physics,
+            paddle_size: state.paddle_left.size,
+            paddle_speed: state.paddle_left.movement_speed,
+            points_to_win: flow.points_to_win,
+            speed_ramp: state.speed_ramp,
+            hit_stop: state.hit_stop,
+            arena: flow.controller.arena.clone(),
+        }
+    }
+
+    pub fn apply(&self, flow: &mut ScreenFlow) {
+        flow.points_to_win = self.points_to_win;
+        flow.controller.arena = self.arena.clone();
+        flow.configure(|state| {
+            state.ball.size = self.ball_size;
+            state.ball.physics = self.physics;
+            state.speed_ramp = self.speed_ramp;
+            state.hit_stop = self.hit_stop;
+            let turned = FPoint::new(self.paddle_size.y(), self.paddle_size.x());
+            for paddle in [&mut state.paddle_left, &mut state.paddle_right] {
+                paddle.size = self.paddle_size;
+                paddle.movement_speed = self.paddle_speed;
+            }
+            for paddle in [state.paddle_top.as_mut(), state.paddle_bottom.as_mut()].into_iter().flatten() {
+                paddle.size = turned;
+                paddle.movement_speed = self.paddle_speed;
+            }
+        });
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Message {
+    Hello { version: u8 },
+    // the host picks the seed both sides simulate with and hands out its setup
+    Welcome { seed: u64, setup: MatchSetup },
+    // `ack` is the first tick the sender has not got the receiver's input for yet
+    Input { tick: u32, input: PaddleInput, ack: u32 },
+    Snapshot(Snapshot),
+    Bye,
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    fn point(&mut self, value: FPoint) {
+        self.f32(value.x());
+        self.f32(value.y());
+    }
+    fn optional_point(&mut self, value: Option<FPoint>) {
+        self.u8(value.is_some() as u8);
+        if let Some(value) = value {
+            self.point(value);
+        }
+    }
+    // 0 for none, the side byte plus one otherwise
+    fn optional_side(&mut self, value: Option<Side>) {
+        self.u8(value.map_or(0, |side| side_byte(side) + 1));
+    }
+    fn setup(&mut self, setup: &MatchSetup) {
+        self.u32(setup.ball_size as u32);
+        let physics = &setup.physics;
+        for value in [physics.horizontal_acc, physics.vertical_acc, physics.restitution_factor, physics.restitution_angle_variance, physics.inherited_velocity, physics.max_velocity, physics.serve_velocity] {
+            self.f32(value);
+        }
+        self.point(setup.paddle_size);
+        self.f32(setup.paddle_speed);
+        self.u32(setup.points_to_win);
+        self.u8(setup.speed_ramp as u8 | (setup.hit_stop as u8) << 1);
+        self.u8(setup.arena.is_some() as u8);
+        if let Some(arena) = &setup.arena {
+            self.arena(arena);
+        }
+    }
+    fn count(&mut self, value: usize) {
+        self.bytes.extend_from_slice(&(value.min(u16::MAX as usize) as u16).to_le_bytes());
+    }
+    fn arena(&mut self, arena: &Arena) {
+        self.count(arena.name.len());
+        self.bytes.extend_from_slice(arena.name.as_bytes());
+        self.count(arena.obstacles.len());
+        for obstacle in &arena.obstacles {
+            match &obstacle.shape {
+                Shape::Rect(rect) => {
+                    self.u8(0);
+                    self.point(FPoint::new(rect.x(), rect.y()));
+                    self.point(FPoint::new(rect.width(), rect.height()));
+                }
+                Shape::Circle { center, radius } => {
+                    self.u8(1);
+                    self.point(*center);
+                    self.f32(*radius);
+                }
+                Shape::Polygon(points) => {
+                    self.u8(2);
+                    self.count(points.len());
+                    for point in points {
+                        self.point(*point);
+                    }
+                }
+                Shape::Wall { from, to, thickness } => {
+                    self.u8(3);
+                    self.point(*from);
+                    self.point(*to);
+                    self.f32(*thickness);
+                }
+            }
+            self.point(obstacle.travel);
+            // in nanoseconds so both sides move the obstacles exactly alike
+            self.u64(obstacle.period.map_or(0, |period| period.as_nanos() as u64));
+        }
+        self.count(arena.portals.len());
+        for portal in &arena.portals {
+            self.point(portal.a);
+            self.point(portal.b);
+            self.f32(portal.radius);
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        if self.bytes.len() < N {
+            return Err(String::from("packet too short"));
+        }
+        let (head, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(head.try_into().expect("split at N"))
+    }
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take::<1>()?[0])
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+    fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_le_bytes(self.take()?))
+    }
+    fn point(&mut self) -> Result<FPoint, String> {
+        Ok(FPoint::new(self.f32()?, self.f32()?))
+    }
+    fn optional_point(&mut self) -> Result<Option<FPoint>, String> {
+        Ok(if self.u8()? == 0 { None } else { Some(self.point()?) })
+    }
+    fn side(&mut self) -> Result<Side, String> {
+        Side::ALL.get(self.u8()? as usize).copied().ok_or_else(|| String::from("unknown side"))
+    }
+    fn optional_side(&mut self) -> Result<Option<Side>, String> {
+        match self.u8()? {
+            0 => Ok(None),
+            byte => Side::ALL.get(byte as usize - 1).copied().map(Some).ok_or_else(|| String::from("unknown side")),
+        }
+    }
+    fn setup(&mut self) -> Result<MatchSetup, String> {
+        let ball_size = self.u32()? as i32;
+        let physics = BallPhysics {
+            horizontal_acc: self.f32()?,
+            vertical_acc: self.f32()?,
+            restitution_factor: self.f32()?,
+            restitution_angle_variance: self.f32()?,
+            inherited_velocity: self.f32()?,
+            max_velocity: self.f32()?,
+            serve_velocity: self.f32()?,
+        };
+        let paddle_size = self.point()?;
+        let paddle_speed = self.f32()?;
+        let points_to_win = self.u32()?;
+        let flags = self.u8()?;
+        let arena = if self.u8()? == 0 { None } else { Some(self.arena()?) };
+        Ok(MatchSetup { ball_size, physics, paddle_size, paddle_speed, points_to_win, speed_ramp: flags & 1 != 0, hit_stop: flags & 2 != 0, arena })
+    }
+    fn count(&mut self) -> Result<usize, String> {
+        Ok(u16::from_le_bytes(self.take()?) as usize)
+    }
+    fn arena(&mut self) -> Result<Arena, String> {
+        let name = (0..self.count()?).map(|_| self.u8()).collect::<Result<Vec<u8>, String>>()?;
+        let mut arena = Arena { name: String::from_utf8_lossy(&name).into_owned(), ..Arena::default() };
+        for _ in 0..self.count()? {
+            let shape = match self.u8()? {
+                0 => {
+                    let (pos, size) = (self.point()?, self.point()?);
+                    Shape::Rect(FRect::new(pos.x(), pos.y(), size.x(), size.y()))
+                }
+                1 => Shape::Circle { center: self.point()?, radius: self.f32()? },
+                2 => Shape::Polygon((0..self.count()?).map(|_| self.point()).collect::<Result<Vec<FPoint>, String>>()?),
+                3 => Shape::Wall { from: self.point()?, to: self.point()?, thickness: self.f32()? },
+                _ => return Err(String::from("unknown obstacle shape")),
+            };
+            let travel = self.point()?;
+            let period = self.u64()?;
+            arena.obstacles.push(Obstacle {
+                shape,
+                travel,
+                period: if period > 0 { Some(Duration::from_nanos(period)) } else { None },
+            });
+        }
+        for _ in 0..self.count()? {
+            arena.portals.push(Portal { a: self.point()?, b: self.point()?, radius: self.f32()? });
+        }
+        Ok(arena)
+    }
+}
+
+fn side_byte(side: Side) -> u8 {
+    match side {
+        Side::Left => 0,
+        Side::Right => 1,
+        Side::Top => 2,
+        Side::Bottom => 3,
+    }
+}
+
+impl Message {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer { bytes: MAGIC.to_vec() };
+        match self {
+            Message::Hello { version } => {
+                writer.u8(0);
+                writer.u8(*version);
+            }
+            Message::Welcome { seed, setup } => {
+                writer.u8(1);
+                writer.u64(*seed);
+                writer.setup(setup);
+            }
+            Message::Input { tick, input, ack } => {
+                writer.u8(2);
+                writer.u32(*tick);
+                writer.u8(input.up as u8 | (input.down as u8) << 1);
+                writer.u32(*ack);
+            }
+            Message::Snapshot(snapshot) => {
+                writer.u8(3);
+                writer.u32(snapshot.tick);
+                writer.point(snapshot.ball_pos);
+                writer.point(snapshot.ball_velocity);
+                writer.f32(snapshot.ball_rotation);
+                writer.point(snapshot.paddle_left);
+                writer.point(snapshot.paddle_right);
+                writer.optional_point(snapshot.paddle_top);
+                writer.optional_point(snapshot.paddle_bottom);
+                writer.optional_side(snapshot.wall);
+                for side in Side::ALL {
+                    writer.u32(snapshot.score.get(side));
+                }
+                writer.u32(snapshot.rally);
+                writer.u32(snapshot.best_rally);
+                writer.f32(snapshot.match_time.as_secs_f32());
+                // the client only needs enough of the screen to draw it
+                match snapshot.screen {
+                    Screen::ServeCountdown { remaining, .. } => {
+                        writer.u8(1);
+                        writer.f32(remaining.as_secs_f32());
+                    }
+                    Screen::PointScored { scorer, remaining } => {
+                        writer.u8(2);
+                        writer.u8(side_byte(scorer));
+                        writer.f32(remaining.as_secs_f32());
+                    }
+                    Screen::MatchOver { winner } => {
+                        writer.u8(3);
+                        writer.u8(side_byte(winner));
+                    }
+                    _ => writer.u8(0),
+                }
+            }
+            Message::Bye => writer.u8(4),
+        }
+        writer.bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Message, String> {
+        let mut reader = Reader { bytes };
+        if &reader.take::<4>()? != MAGIC {
+            return Err(String::from("not a peng packet"));
+        }
+
+        match reader.u8()? {
+            0 => Ok(Message::Hello { version: reader.u8()? }),
+            1 => Ok(Message::Welcome { seed: reader.u64()?, setup: reader.setup()? }),
+            2 => {
+                let tick = reader.u32()?;
+                let buttons = reader.u8()?;
+                let ack = reader.u32()?;
+                Ok(Message::Input { tick, input: PaddleInput { up: buttons & 1 != 0, down: buttons & 2 != 0 }, ack })
+            }
+            3 => {
+                let tick = reader.u32()?;
+                let ball_pos = reader.point()?;
+                let ball_velocity = reader.point()?;
+                let ball_rotation = reader.f32()?;
+                let paddle_left = reader.point()?;
+                let paddle_right = reader.point()?;
+                let paddle_top = reader.optional_point()?;
+                let paddle_bottom = reader.optional_point()?;
+                let wall = reader.optional_side()?;
+                let score = Score { left: reader.u32()?, right: reader.u32()?, top: reader.u32()?, bottom: reader.u32()? };
+                let rally = reader.u32()?;
+                let best_rally = reader.u32()?;
+                let match_time = Duration::from_secs_f32(reader.f32()?.max(0.0));
+                let screen = match reader.u8()? {
+                    1 => Screen::ServeCountdown { remaining: Duration::from_secs_f32(reader.f32()?.max(0.0)), velocity: FPoint::new(0.0, 0.0) },
+                    2 => Screen::PointScored { scorer: reader.side()?, remaining: Duration::from_secs_f32(reader.f32()?.max(0.0)) },
+                    3 => Screen::MatchOver { winner: reader.side()? },
+                    _ => Screen::Rally,
+                };
+                Ok(Message::Snapshot(Snapshot { tick, ball_pos, ball_velocity, ball_rotation, paddle_left, paddle_right, paddle_top, paddle_bottom, wall, score, rally, best_rally, match_time, screen }))
+            }
+            4 => Ok(Message::Bye),
+            kind => Err(format!("unknown message kind {}", kind)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected(&'static str),
+}
+
+/// Artificial network trouble applied to everything a `Connection` sends.
+#[derive(Copy, Clone, Default, PartialEq, Debug)]
+pub struct LinkConditions {
+    pub latency: Duration,
+    // 0 to 1, the chance a packet is dropped
+    pub loss: f32,
+}
+
+/// One peer over a non-blocking UDP socket. Takes care of the handshake, the timeout and
+/// saying goodbye, game messages are handed out by `poll`.
+pub struct Connection {
+    socket: UdpSocket,
+    peer: Option<SocketAddr>,
+    is_host: bool,
+    pub state: ConnectionState,
+    pub timeout: Duration,
+    last_received: Instant,
+    last_hello: Option<Instant>,
+    pub seed: u64,
+    // the host's setup, the host sends it in the welcome and the client gets it from there
+    pub setup: Option<MatchSetup>,
+    pub conditions: LinkConditions,
+    // packets held back by the simulated latency, in the order they are due
+    delayed: VecDeque<(Instant, Vec<u8>, SocketAddr)>,
+    loss_rng: GameRng,
+}
+
+impl Connection {
+    /// Waits for a client on `bind_addr`, e.g. `0.0.0.0:7777`. The host waits as long as it takes.
+    pub fn host<A: ToSocketAddrs>(bind_addr: A, setup: MatchSetup) -> Result<Connection, String> {
+        if (Message::Welcome { seed: 0, setup: setup.clone() }).encode().len() > MAX_PACKET_SIZE {
+            return Err(String::from("the arena has too many obstacles to play over the network"));
+        }
+        let mut connection = Connection::open(bind_addr, None)?;
+        connection.setup = Some(setup);
+        Ok(connection)
+    }
+
+    /// Starts the handshake with the host at `host_addr`.
+    pub fn join<A: ToSocketAddrs>(host_addr: A) -> Result<Connection, String> {
+        let peer = host_addr.to_socket_addrs().map_err(|e| e.to_string())?.next().ok_or("could not resolve the host address")?;
+        let bind_addr = if peer.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        Connection::open(bind_addr, Some(peer))
+    }
+
+    fn open<A: ToSocketAddrs>(bind_addr: A, peer: Option<SocketAddr>) -> Result<Connection, String> {
+        let socket = UdpSocket::bind(bind_addr).map_err(|e| e.to_string())?;
+        socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(Connection {
+            socket,
+            peer,
+            is_host: peer.is_none(),
+            state: ConnectionState::Connecting,
+            timeout: TIMEOUT,
+            last_received: Instant::now(),
+            last_hello: None,
+            // only the host's seed is used, the client takes it from the welcome
+            seed: rand::random(),
+            setup: None,
+            conditions: LinkConditions::default(),
+            delayed: VecDeque::new(),
+            loss_rng: GameRng::new(rand::random()),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, String> {
+        self.socket.local_addr().map_err(|e| e.to_string())
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state == ConnectionState::Connected
+    }
+
+    fn send_to_peer(&mut self, message: &Message) {
+        if let Some(peer) = self.peer {
+            self.transmit(message.encode(), peer);
+        }
+    }
+
+    // lost packets are fine, errors only mean the peer is not there yet
+    fn transmit(&mut self, bytes: Vec<u8>, to: SocketAddr) {
+        if self.conditions.loss > 0.0 && self.loss_rng.next_f32() < self.conditions.loss {
+            return;
+        }
+        if self.conditions.latency.is_zero() {
+            let _ = self.socket.send_to(&bytes, to);
+        } else {
+            self.delayed.push_back((Instant::now() + self.conditions.latency, bytes, to));
+        }
+    }
+
+    fn flush_delayed(&mut self, force: bool) {
+        while let Some((due, _, _)) = self.delayed.front() {
+            if !force && *due > Instant::now() {
+                break;
+            }
+            let (_, bytes, to) = self.delayed.pop_front().expect("front exists");
+            let _ = self.socket.send_to(&bytes, to);
+        }
+    }
+
+    fn send_welcome(&mut self) {
+        if let Some(setup) = self.setup.clone() {
+            self.send_to_peer(&Message::Welcome { seed: self.seed, setup });
+        }
+    }
+
+    /// Sends a game message once connected.
+    pub fn send(&mut self, message: &Message) {
+        if self.is_connected() {
+            self.send_to_peer(message);
+        }
+    }
+
+    pub fn close(&mut self) {
+        if self.is_connected() {
+            self.send_to_peer(&Message::Bye);
+        }
+        self.flush_delayed(true);
+        if !matches!(self.state, ConnectionState::Disconnected(_)) {
+            self.state = ConnectionState::Disconnected("CONNECTION CLOSED");
+        }
+    }
+
+    /// Reads everything that arrived since the last call and returns the game messages.
+    pub fn poll(&mut self) -> Vec<Message> {
+        let mut messages = Vec::new();
+        if let ConnectionState::Disconnected(_) = self.state {
+            return messages;
+        }
+        self.flush_delayed(false);
+
+        if !self.is_host && self.state == ConnectionState::Connecting
+            && self.last_hello.is_none_or(|sent| sent.elapsed() >= HELLO_INTERVAL) {
+            self.send_to_peer(&Message::Hello { version: PROTOCOL_VERSION });
+            self.last_hello = Some(Instant::now());
+        }
+
+        let mut buffer = [0u8; MAX_PACKET_SIZE];
+        // WouldBlock means there is nothing left, other errors are treated the same and
+        // the timeout catches a peer that is really gone
+        while let Ok((length, from)) = self.socket.recv_from(&mut buffer) {
+            let message = match Message::decode(&buffer[..length]) {
+                Ok(message) => message,
+                Err(_) => continue,
+            };
+
+            match (self.state, message) {
+                // the host takes the first client that says hello
+                (ConnectionState::Connecting, Message::Hello { version }) if self.is_host => {
+                    if version != PROTOCOL_VERSION {
+                        self.transmit(Message::Bye.encode(), from);
+                        continue;
+                    }
+                    self.peer = Some(from);
+                    self.state = ConnectionState::Connected;
+                    self.send_welcome();
+                }
+                _ if Some(from) != self.peer => continue,
+                // the welcome got lost, the client is still asking
+                (ConnectionState::Connected, Message::Hello { .. }) if self.is_host => self.send_welcome(),
+                (ConnectionState::Connecting, Message::Welcome { seed, setup }) => {
+                    self.seed = seed;
+                    self.setup = Some(setup);
+                    self.state = ConnectionState::Connected;
+                }
+                (ConnectionState::Connecting, Message::Bye) => self.state = ConnectionState::Disconnected("HOST REFUSED THE CONNECTION"),
+                (_, Message::Bye) => self.state = ConnectionState::Disconnected("THE OTHER PLAYER LEFT"),
+                (ConnectionState::Connected, message @ (Message::Input { .. } | Message::Snapshot(_))) => messages.push(message),
+                _ => {}
+            }
+            if Some(from) == self.peer {
+                self.last_received = Instant::now();
+            }
+        }
+
+        let waiting_for_client = self.is_host && self.state == ConnectionState::Connecting;
+        if !waiting_for_client && self.last_received.elapsed() > self.timeout {
+            self.state = ConnectionState::Disconnected(if self.is_connected() { "CONNECTION TIMED OUT" } else { "NO ANSWER FROM HOST" });
+        }
+        messages
+    }
+}
+
+/// Runs a network match on top of a `ScreenFlow`. The host simulates with the client's
+/// paddle on the right driven by a `NetworkPaddleController`, the client shows the
+/// snapshots it receives.
+pub struct NetSession {
+    pub connection: Connection,
+    role: NetworkRole,
+    remote_input: Option<Rc<Cell<PaddleInput>>>,
+    tick: u32,
+    last_tick_received: u32,
+    last_send: Option<Instant>,
+}
+
+impl NetSession {
+    pub fn host<A: ToSocketAddrs>(bind_addr: A, flow: &mut ScreenFlow) -> Result<NetSession, String> {
+        let connection = Connection::host(bind_addr, MatchSetup::capture(flow))?;
+        let (network_controller, remote_input) = NetworkPaddleController::new();
+        flow.start_network_match(
+            NetworkRole::Host,
+            Box::new(PlayerPaddleController::new(Keycode::W, Keycode::S)),
+            Box::new(network_controller),
+        );
+        Ok(NetSession::new(connection, NetworkRole::Host, Some(remote_input)))
+    }
+
+    pub fn join<A: ToSocketAddrs>(host_addr: A, flow: &mut ScreenFlow) -> Result<NetSession, String> {
+        let connection = Connection::join(host_addr)?;
+        // the client never simulates, its controllers are never asked
+        let (left, _) = NetworkPaddleController::new();
+        let (right, _) = NetworkPaddleController::new();
+        flow.start_network_match(NetworkRole::Client, Box::new(left), Box::new(right));
+        Ok(NetSession::new(connection, NetworkRole::Client, None))
+    }
+
+    fn new(connection: Connection, role: NetworkRole, remote_input: Option<Rc<Cell<PaddleInput>>>) -> NetSession {
+        NetSession {
+            connection,
+            role,
+            remote_input,
+            tick: 0,
+            last_tick_received: 0,
+            last_send: None,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(self.connection.state, ConnectionState::Disconnected(_))
+    }
+
+    /// Exchanges messages and keeps `flow` in step with the connection, call once per frame.
+    pub fn update(&mut self, flow: &mut ScreenFlow, local_input: PaddleInput) {
+        if self.is_closed() {
+            return;
+        }
+        // the player left through the menus
+        if flow.network.is_none() || matches!(flow.screen, Screen::Disconnected { .. } | Screen::Title) {
+            self.connection.close();
+            return;
+        }
+
+        let was_connected = self.connection.is_connected();
+        for message in self.connection.poll() {
+            match message {
+                Message::Input { tick, input, .. } if tick >= self.last_tick_received => {
+                    self.last_tick_received = tick;
+                    if let Some(remote_input) = &self.remote_input {
+                        remote_input.set(input);
+                    }
+                }
+                // late snapshots are dropped, newer ones already moved things further
+                Message::Snapshot(snapshot) if snapshot.tick >= self.last_tick_received => {
+                    self.last_tick_received = snapshot.tick;
+                    snapshot.show(flow);
+                }
+                _ => {}
+            }
+        }
+
+        if let ConnectionState::Disconnected(reason) = self.connection.state {
+            flow.change_screen(Screen::Disconnected { reason });
+            return;
+        }
+        if !was_connected && self.connection.is_connected() {
+            match (self.role, &self.connection.setup) {
+                (NetworkRole::Host, _) => flow.change_screen(Screen::ServeCountdown { remaining: Duration::ZERO, velocity: FPoint::new(0.0, 0.0) }),
+                // the snapshots only carry positions, sizes and rules are the host's from here on
+                (_, Some(setup)) => setup.apply(flow),
+                _ => {}
+            }
+        }
+
+        if self.last_send.is_some_and(|sent| sent.elapsed() < SEND_INTERVAL) {
+            return;
+        }
+        self.last_send = Some(Instant::now());
+        self.tick += 1;
+        let message = match self.role {
+            NetworkRole::Host => Message::Snapshot(Snapshot::capture(self.tick, &flow.state, flow.screen)),
+            _ => Message::Input { tick: self.tick, input: local_input, ack: self.last_tick_received },
+        };
+        self.connection.send(&message);
+    }
+}
+
+impl Drop for NetSession {
+    fn drop(&mut self) {
+        self.connection.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::arena::Arena;
+    use crate::net::{Connection, ConnectionState, MatchSetup, Message, NetSession, PaddleInput, Snapshot};
+    use crate::screens::{Screen, ScreenFlow};
+    use crate::test_support::{key, test_flow, test_state};
+    use crate::{Score, Side};
+    use sdl2::keyboard::Keycode;
+    use sdl2::rect::FPoint;
+    use std::time::Duration;
+
+    // keeps both sides exchanging packets until `done` or a second passed
+    fn pump(host: &mut NetSession, host_flow: &mut ScreenFlow, client: &mut NetSession, client_flow: &mut ScreenFlow, input: PaddleInput, done: impl Fn(&ScreenFlow, &ScreenFlow) -> bool) {
+        for _ in 0..200 {
+            host_flow.update(Duration::from_millis(5)).unwrap();
+            host.update(host_flow, PaddleInput::default());
+            client.update(client_flow, input);
+            if done(host_flow, client_flow) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn encode_decode_test() {
+        let snapshot = Snapshot {
+            tick: 42,
+            ball_pos: FPoint::new(1.0, 2.0),
+            ball_velocity: FPoint::new(-3.0, 4.5),
+            ball_rotation: 0.25,
+            paddle_left: FPoint::new(20.0, 300.0),
+            paddle_right: FPoint::new(780.0, 120.0),
+            paddle_top: Some(FPoint::new(400.0, 20.0)),
+            paddle_bottom: None,
+            wall: Some(Side::Right),
+            score: Score { left: 3, right: 7, top: 1, bottom: 0 },
+            rally: 9,
+            best_rally: 12,
+            match_time: Duration::from_secs(61),
+            screen: Screen::PointScored { scorer: Side::Top, remaining: Duration::from_millis(500) },
+        };
+        let arena = Arena::parse("
+            name = test
+            obstacle = rect 100 100 50 20
+            obstacle = circle 400 300 40
+            obstacle = polygon 300 450 340 520 260 520
+            obstacle = wall 200 80 300 160 12 moves 0 100 2.537
+            portal = 250 300 550 300 20
+        ");
+        let setup = MatchSetup { points_to_win: 3, hit_stop: false, arena: Some(arena.unwrap()), ..MatchSetup::capture(&test_flow()) };
+        let messages = [
+            Message::Hello { version: 1 },
+            Message::Welcome { seed: 0xDEAD_BEEF_0BAD_CAFE, setup },
+            Message::Input { tick: 7, input: PaddleInput { up: false, down: true }, ack: 5 },
+            Message::Snapshot(snapshot),
+            Message::Bye,
+        ];
+        for message in messages {
+            assert_eq!(Message::decode(&message.encode()).unwrap(), message);
+        }
+
+        assert!(Message::decode(b"PONG\x01").is_err());
+        assert!(Message::decode(&Message::Snapshot(snapshot).encode()[..20]).is_err());
+    }
+
+    #[test]
+    fn snapshot_carries_wall_practice_test() {
+        let mut state = test_state();
+        state.remove_paddle(Side::Right);
+        state.best_rally = 12;
+
+        let mut shown = test_state();
+        Snapshot::capture(0, &state, Screen::Rally).apply(&mut shown);
+        assert_eq!(shown.wall, Some(Side::Right));
+        assert!(shown.paddle(Side::Right).is_none());
+        assert_eq!(shown.best_rally, 12);
+    }
+
+    #[test]
+    fn loopback_match_test() {
+        let mut host_flow = test_flow();
+        let mut client_flow = test_flow();
+        let mut host = NetSession::host("127.0.0.1:0", &mut host_flow).unwrap();
+        let host_addr = host.connection.local_addr().unwrap();
+        let mut client = NetSession::join(host_addr, &mut client_flow).unwrap();
+        assert_eq!(host_flow.screen, Screen::Connecting);
+
+        // the handshake starts the serve on the host, the client follows through the snapshots
+        pump(&mut host, &mut host_flow, &mut client, &mut client_flow, PaddleInput::default(), |_, client| {
+            matches!(client.screen, Screen::ServeCountdown { .. })
+        });
+        assert!(host.connection.is_connected());
+        assert!(matches!(client_flow.screen, Screen::ServeCountdown { .. }));
+
+        // the client's input moves the right paddle on the host
+        let start = host_flow.state.paddle_right.pos.y();
+        pump(&mut host, &mut host_flow, &mut client, &mut client_flow, PaddleInput { up: false, down: true }, |host, client| {
+            host.state.paddle_right.pos.y() > start + 20.0 && client.state.paddle_right.pos.y() > start + 20.0
+        });
+        assert!(host_flow.state.paddle_right.pos.y() > start + 20.0);
+        assert!(client_flow.state.paddle_right.pos.y() > start + 20.0);
+
+        // leaving on one side shows the disconnect screen on the other
+        client_flow.handle_event(&key(Keycode::Escape)).unwrap();
+        pump(&mut host, &mut host_flow, &mut client, &mut client_flow, PaddleInput::default(), |host, _| {
+            matches!(host.screen, Screen::Disconnected { .. })
+        });
+        assert_eq!(host_flow.screen, Screen::Disconnected { reason: "THE OTHER PLAYER LEFT" });
+        assert!(client.is_closed());
+    }
+
+    #[test]
+    fn arena_too_big_test() {
+        let mut flow = test_flow();
+        let obstacles: Vec<String> = (0..100).map(|i| format!("obstacle = circle {} 300 5", i * 8)).collect();
+        flow.controller.arena = Some(Arena::parse(&obstacles.join("\n")).unwrap());
+        assert!(Connection::host("127.0.0.1:0", MatchSetup::capture(&flow)).is_err());
+    }
+
+    #[test]
+    fn client_takes_host_setup_test() {
+        let mut host_flow = test_flow();
+        host_flow.points_to_win = 3;
+        host_flow.configure(|state| {
+            state.ball.size = 30;
+            state.ball.physics.max_velocity = 1500.0;
+            state.paddle_left.set_length(180.0);
+            state.paddle_right.set_length(180.0);
+            state.hit_stop = false;
+        });
+        let mut client_flow = test_flow();
+        let mut host = NetSession::host("127.0.0.1:0", &mut host_flow).unwrap();
+        let mut client = NetSession::join(host.connection.local_addr().unwrap(), &mut client_flow).unwrap();
+
+        pump(&mut host, &mut host_flow, &mut client, &mut client_flow, PaddleInput::default(), |_, client| {
+            matches!(client.screen, Screen::ServeCountdown { .. })
+        });
+        assert_eq!(MatchSetup::capture(&client_flow), MatchSetup::capture(&host_flow));
+        assert_eq!(client_flow.state.paddle_right.length(), 180.0);
+        assert_eq!(client_flow.state.ball.size, 30);
+    }
+
+    #[test]
+    fn timeout_test() {
+        // nobody listens on the other end
+        let unused = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut connection = Connection::join(unused.local_addr().unwrap()).unwrap();
+        connection.timeout = Duration::from_millis(50);
+        connection.poll();
+        assert_eq!(connection.state, ConnectionState::Connecting);
+
+        std::thread::sleep(Duration::from_millis(60));
+        connection.poll();
+        assert_eq!(connection.state, ConnectionState::Disconnected("NO ANSWER FROM HOST"));
+    }
+}
@@ -0,0 +1,277 @@
+#![allow(dead_code)]
+
+use sdl2::pixels::Color;
+use sdl2::rect::FPoint;
+use sdl2::render::{BlendMode, RenderTarget};
+use sdl2::sys::SDL_Vertex;
+
+use crate::draw_primitives::draw_primitives::{render_geometry, vertex};
+
+/// Three stop color ramp a particle runs through over its lifetime.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ColorRamp {
+    pub start: Color,
+    pub middle: Color,
+    pub end: Color,
+}
+
+impl ColorRamp {
+    // solid color that fades out towards the end of the particle's life
+    pub fn fade_out(color: Color) -> ColorRamp {
+        ColorRamp {
+            start: color,
+            middle: Color::RGBA(color.r, color.g, color.b, color.a / 2),
+            end: Color::RGBA(color.r, color.g, color.b, 0),
+        }
+    }
+
+    pub fn color_at(&self, t: f32) -> Color {
+        let lerp = |from: Color, to: Color, t: f32| {
+            let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+            Color::RGBA(channel(from.r, to.r), channel(from.g, to.g), channel(from.b, to.b), channel(from.a, to.a))
+        };
+        let t = t.clamp(0.0, 1.0);
+        if t < 0.5 {
+            lerp(self.start, self.middle, t * 2.0)
+        } else {
+            lerp(self.middle, self.end, (t - 0.5) * 2.0)
+        }
+    }
+}
+
+/// Describes a burst of particles, ranges are (min, max) and picked uniformly per particle.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ParticleEmitter {
+    pub count: u32,
+    pub speed: (f32, f32),
+    // total angle in radians the particles fan out over, centered on the burst direction
+    pub spread: f32,
+    pub lifetime: (f32, f32),
+    pub size: (f32, f32),
+    pub gravity: FPoint,
+    pub drag: f32,
+    pub colors: ColorRamp,
+}
+
+impl ParticleEmitter {
+    pub fn sparks(color: Color) -> ParticleEmitter {
+        ParticleEmitter {
+            count: 16,
+            speed: (150.0, 400.0),
+            spread: std::f32::consts::PI * 0.75,
+            lifetime: (0.2, 0.5),
+            size: (2.0, 4.0),
+            gravity: FPoint::new(0.0, 300.0),
+            drag: 2.0,
+            colors: ColorRamp {
+                start: Color::WHITE,
+                middle: color,
+                end: Color::RGBA(color.r, color.g, color.b, 0),
+            },
+        }
+    }
+
+    pub fn dust(color: Color) -> ParticleEmitter {
+        ParticleEmitter {
+            count: 8,
+            speed: (30.0, 120.0),
+            spread: std::f32::consts::PI,
+            lifetime: (0.2, 0.4),
+            size: (2.0, 5.0),
+            gravity: FPoint::new(0.0, 0.0),
+            drag: 4.0,
+            colors: ColorRamp::fade_out(color),
+        }
+    }
+
+    pub fn firework(color: Color) -> ParticleEmitter {
+        ParticleEmitter {
+            count: 64,
+            speed: (100.0, 500.0),
+            spread: std::f32::consts::PI * 2.0,
+            lifetime: (0.5, 1.2),
+            size: (3.0, 6.0),
+            gravity: FPoint::new(0.0, 200.0),
+            drag: 1.5,
+            colors: ColorRamp {
+                start: Color::WHITE,
+                middle: color,
+                end: Color::RGBA(color.r / 2, color.g / 2, color.b / 2, 0),
+            },
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Particle {
+    pos: FPoint,
+    velocity: FPoint,
+    age: f32,
+    lifetime: f32,
+    size: f32,
+    gravity: FPoint,
+    drag: f32,
+    colors: ColorRamp,
+}
+
+/// Fixed capacity particle pool. Live particles are kept packed at the front of the pool,
+/// dead ones are swapped out, so nothing is allocated after construction.
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+    live: usize,
+}
+
+impl ParticleSystem {
+    pub fn new(capacity: usize) -> ParticleSystem {
+        let unused = Particle {
+            pos: FPoint::new(0.0, 0.0),
+            velocity: FPoint::new(0.0, 0.0),
+            age: 0.0,
+            lifetime: 0.0,
+            size: 0.0,
+            gravity: FPoint::new(0.0, 0.0),
+            drag: 0.0,
+            colors: ColorRamp::fade_out(Color::WHITE),
+        };
+        ParticleSystem {
+            particles: vec![unused; capacity],
+            live: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn live_count(&self) -> usize {
+        self.live
+    }
+
+    pub fn clear(&mut self) {
+        self.live = 0;
+    }
+
+    /// Spawns `emitter.count` particles at `pos` heading towards `direction` (radians).
+    /// Particles that do not fit into the pool are dropped.
+    pub fn burst(&mut self, emitter: &ParticleEmitter, pos: FPoint, direction: f32) {
+        let random_in = |(min, max): (f32, f32)| min + rand::random::<f32>() * (max - min);
+
+        for _ in 0..emitter.count {
+            if self.live == self.particles.len() {
+                return;
+            }
+
+            let angle = direction + (rand::random::<f32>() - 0.5) * emitter.spread;
+            let speed = random_in(emitter.speed);
+            self.particles[self.live] = Particle {
+                pos,
+                velocity: FPoint::new(speed * angle.cos(), speed * angle.sin()),
+                age: 0.0,
+                lifetime: random_in(emitter.lifetime),
+                size: random_in(emitter.size),
+                gravity: emitter.gravity,
+                drag: emitter.drag,
+                colors: emitter.colors,
+            };
+            self.live += 1;
+        }
+    }
+
+    pub fn update(&mut self, delta_t: std::time::Duration) {
+        let dt = delta_t.as_secs_f32();
+        let mut i = 0;
+        while i < self.live {
+            let particle = &mut self.particles[i];
+            particle.age += dt;
+
+            if particle.age >= particle.lifetime {
+                self.live -= 1;
+                self.particles.swap(i, self.live);
+                continue;
+            }
+
+            let damping = (1.0 - particle.drag * dt).max(0.0);
+            particle.velocity = FPoint::new(
+                (particle.velocity.x() + particle.gravity.x() * dt) * damping,
+                (particle.velocity.y() + particle.gravity.y() * dt) * damping,
+            );
+            particle.pos = FPoint::new(
+                particle.pos.x() + particle.velocity.x() * dt,
+                particle.pos.y() + particle.velocity.y() * dt,
+            );
+            i += 1;
+        }
+    }
+
+    // all particles go out in a single geometry call
+    pub fn draw<T: RenderTarget>(&self, canvas: &mut sdl2::render::Canvas<T>) -> Result<(), String> {
+        if self.live == 0 {
+            return Ok(());
+        }
+
+        let no_tex = FPoint::new(0.0, 0.0);
+        let mut vertices: Vec<SDL_Vertex> = Vec::with_capacity(self.live * 4);
+        let mut indices: Vec<i32> = Vec::with_capacity(self.live * 6);
+
+        for particle in &self.particles[..self.live] {
+            let t = particle.age / particle.lifetime;
+            let color = particle.colors.color_at(t);
+            let half = particle.size * (1.0 - t * 0.5) / 2.0;
+            let (x, y) = (particle.pos.x(), particle.pos.y());
+
+            let first = vertices.len() as i32;
+            vertices.push(vertex(FPoint::new(x - half, y - half), color, no_tex));
+            vertices.push(vertex(FPoint::new(x + half, y - half), color, no_tex));
+            vertices.push(vertex(FPoint::new(x + half, y + half), color, no_tex));
+            vertices.push(vertex(FPoint::new(x - half, y + half), color, no_tex));
+            indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+
+        let blend_mode = canvas.blend_mode();
+        canvas.set_blend_mode(BlendMode::Blend);
+        let result = render_geometry(canvas, None, vertices.as_slice(), indices.as_slice());
+        canvas.set_blend_mode(blend_mode);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::particles::{ParticleEmitter, ParticleSystem};
+    use sdl2::pixels::Color;
+    use sdl2::rect::FPoint;
+    use std::time::Duration;
+
+    #[test]
+    fn pool_capacity_test() {
+        let mut particles = ParticleSystem::new(20);
+        let emitter = ParticleEmitter::sparks(Color::GREEN);
+
+        particles.burst(&emitter, FPoint::new(0.0, 0.0), 0.0);
+        assert_eq!(particles.live_count(), 16);
+
+        particles.burst(&emitter, FPoint::new(0.0, 0.0), 0.0);
+        assert_eq!(particles.live_count(), 20);
+        assert_eq!(particles.capacity(), 20);
+    }
+
+    #[test]
+    fn lifetime_test() {
+        let mut particles = ParticleSystem::new(100);
+        let mut emitter = ParticleEmitter::dust(Color::GREEN);
+        emitter.lifetime = (0.5, 0.5);
+
+        particles.burst(&emitter, FPoint::new(0.0, 0.0), 0.0);
+        particles.update(Duration::from_millis(100));
+        emitter.lifetime = (1.0, 1.0);
+        particles.burst(&emitter, FPoint::new(0.0, 0.0), 0.0);
+        assert_eq!(particles.live_count(), 16);
+
+        // the first burst dies at 0.5s, the second one lives on
+        particles.update(Duration::from_millis(450));
+        assert_eq!(particles.live_count(), 8);
+
+        particles.update(Duration::from_millis(600));
+        assert_eq!(particles.live_count(), 0);
+    }
+}
@@ -0,0 +1,294 @@
+#![allow(dead_code)]
+
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::FPoint;
+use sdl2::render::{BlendMode, Canvas, RenderTarget, Texture, TextureCreator};
+use sdl2::sys::SDL_Vertex;
+
+use crate::draw_primitives::draw_primitives::{render_geometry, vertex};
+
+// the distortion mesh has this many cells along each axis
+const MESH_RESOLUTION: u32 = 24;
+// the bloom buffer is this many times smaller than the frame, upscaling it blurs it
+const BLOOM_DOWNSCALE: u32 = 4;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct CrtSettings {
+    // 0 leaves every line lit, 1 blacks out every other line
+    pub scanlines: f32,
+    // darkening towards the corners, 0 to 1
+    pub vignette: f32,
+    // strength of the blurred glow added on top, 0 to 1
+    pub bloom: f32,
+    // barrel distortion, 0 is a flat screen
+    pub curvature: f32,
+}
+
+impl CrtSettings {
+    pub fn new() -> CrtSettings {
+        CrtSettings {
+            scanlines: 0.35,
+            vignette: 0.6,
+            bloom: 0.35,
+            curvature: 0.08,
+        }
+    }
+}
+
+impl Default for CrtSettings {
+    fn default() -> Self {
+        CrtSettings::new()
+    }
+}
+
+/// Software CRT look: the frame is drawn into an offscreen texture and composited back
+/// through a curved mesh with a glow pass and a scanline/vignette overlay. Only uses
+/// render targets and `SDL_RenderGeometry`, so it works with the software renderer.
+pub struct CrtFilter<'a, C> {
+    pub enabled: bool,
+    pub settings: CrtSettings,
+    texture_creator: &'a TextureCreator<C>,
+    scene: Option<Texture<'a>>,
+    bloom: Option<Texture<'a>>,
+    overlay: Option<Texture<'a>>,
+    size: (u32, u32),
+    overlay_settings: Option<CrtSettings>,
+}
+
+impl<'a, C> CrtFilter<'a, C> {
+    pub fn new(texture_creator: &'a TextureCreator<C>) -> CrtFilter<'a, C> {
+        CrtFilter {
+            enabled: true,
+            settings: CrtSettings::new(),
+            texture_creator,
+            scene: None,
+            bloom: None,
+            overlay: None,
+            size: (0, 0),
+            overlay_settings: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    fn prepare(&mut self, width: u32, height: u32) -> Result<(), String> {
+        if self.size != (width, height) || self.scene.is_none() {
+            let target = |w: u32, h: u32| {
+                self.texture_creator
+                    .create_texture_target(PixelFormatEnum::RGBA8888, w.max(1), h.max(1))
+                    .map_err(|e| e.to_string())
+            };
+            self.scene = Some(target(width, height)?);
+            self.bloom = Some(target(width / BLOOM_DOWNSCALE, height / BLOOM_DOWNSCALE)?);
+            self.size = (width, height);
+            self.overlay_settings = None;
+        }
+
+        if self.overlay_settings != Some(self.settings) {
+            self.overlay = Some(self.build_overlay(width, height)?);
+            self.overlay_settings = Some(self.settings);
+        }
+        Ok(())
+    }
+
+    // scanlines and vignette baked into one alpha blended black texture
+    fn build_overlay(&self, width: u32, height: u32) -> Result<Texture<'a>, String> {
+        // SDL refuses empty textures, a minimized window can report a 0x0 output
+        let (width, height) = (width.max(1), height.max(1));
+        let mut texture = self.texture_creator
+            .create_texture_static(PixelFormatEnum::RGBA32, width, height)
+            .map_err(|e| e.to_string())?;
+        texture.set_blend_mode(BlendMode::Blend);
+
+        let mut pixels: Vec<u8> = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            let scanline = if y % 2 == 1 { self.settings.scanlines } else { 0.0 };
+            for x in 0..width {
+                let dx = x as f32 / width as f32 * 2.0 - 1.0;
+                let dy = y as f32 / height as f32 * 2.0 - 1.0;
+                let edge = ((dx * dx + dy * dy) / 2.0).clamp(0.0, 1.0);
+                let vignette = self.settings.vignette * edge * edge;
+                let darkness = 1.0 - (1.0 - scanline) * (1.0 - vignette);
+                pixels.extend_from_slice(&[0, 0, 0, (darkness.clamp(0.0, 1.0) * 255.0) as u8]);
+            }
+        }
+        texture.update(None, pixels.as_slice(), (width * 4) as usize).map_err(|e| e.to_string())?;
+        Ok(texture)
+    }
+
+    // grid covering the whole frame with positions pushed through the barrel distortion
+    fn mesh(&self, width: f32, height: f32, color: Color) -> (Vec<SDL_Vertex>, Vec<i32>) {
+        let k = self.settings.curvature;
+        let mut vertices: Vec<SDL_Vertex> = Vec::new();
+        for j in 0..=MESH_RESOLUTION {
+            for i in 0..=MESH_RESOLUTION {
+                let u = i as f32 / MESH_RESOLUTION as f32;
+                let v = j as f32 / MESH_RESOLUTION as f32;
+                let (x, y) = (u * 2.0 - 1.0, v * 2.0 - 1.0);
+                // edge midpoints stay on the border, corners are pulled in
+                let factor = (1.0 + k) / (1.0 + k * (x * x + y * y));
+                let position = FPoint::new((x * factor + 1.0) / 2.0 * width, (y * factor + 1.0) / 2.0 * height);
+                vertices.push(vertex(position, color, FPoint::new(u, v)));
+            }
+        }
+
+        let row = MESH_RESOLUTION as i32 + 1;
+        let mut indices: Vec<i32> = Vec::new();
+        for j in 0..MESH_RESOLUTION as i32 {
+            for i in 0..MESH_RESOLUTION as i32 {
+                let top_left = j * row + i;
+                indices.extend_from_slice(&[top_left, top_left + 1, top_left + row + 1, top_left, top_left + row + 1, top_left + row]);
+            }
+        }
+        (vertices, indices)
+    }
+
+    /// Runs `draw` and presents its output through the filter. When the filter is disabled
+    /// `draw` renders straight into `canvas`.
+    pub fn render<T: RenderTarget, F>(&mut self, canvas: &mut Canvas<T>, draw: F) -> Result<(), String>
+    where
+        F: FnOnce(&mut Canvas<T>) -> Result<(), String>,
+    {
+        if !self.enabled {
+            return draw(canvas);
+        }
+
+        // with a logical size set the frame is built at that size and SDL letterboxes the composite
+        let (width, height) = match canvas.logical_size() {
+            (0, 0) => canvas.output_size()?,
+            logical_size => logical_size,
+        };
+        self.prepare(width, height)?;
+
+        let mut draw_result = Ok(());
+        if let Some(scene) = self.scene.as_mut() {
+            canvas.with_texture_canvas(scene, |texture_canvas| {
+                draw_result = draw(texture_canvas);
+            }).map_err(|e| e.to_string())?;
+        }
+        draw_result?;
+
+        let mut copy_result = Ok(());
+        if let (Some(scene), Some(bloom)) = (self.scene.as_ref(), self.bloom.as_mut()) {
+            canvas.with_texture_canvas(bloom, |texture_canvas| {
+                texture_canvas.set_draw_color(Color::BLACK);
+                texture_canvas.clear();
+                copy_result = texture_canvas.copy(scene, None, None);
+            }).map_err(|e| e.to_string())?;
+        }
+        copy_result?;
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.clear();
+
+        let (vertices, indices) = self.mesh(width as f32, height as f32, Color::WHITE);
+        if let Some(scene) = self.scene.as_mut() {
+            scene.set_blend_mode(BlendMode::None);
+            render_geometry(canvas, Some(scene), vertices.as_slice(), indices.as_slice())?;
+        }
+
+        let glow = (self.settings.bloom.clamp(0.0, 1.0) * 255.0) as u8;
+        let (glow_vertices, _) = self.mesh(width as f32, height as f32, Color::RGBA(glow, glow, glow, 255));
+        if let Some(bloom) = self.bloom.as_mut() {
+            bloom.set_blend_mode(BlendMode::Add);
+            render_geometry(canvas, Some(bloom), glow_vertices.as_slice(), indices.as_slice())?;
+        }
+
+        if let Some(overlay) = self.overlay.as_ref() {
+            render_geometry(canvas, Some(overlay), vertices.as_slice(), indices.as_slice())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::post_process::CrtFilter;
+    use sdl2::pixels::{Color, PixelFormatEnum};
+    use sdl2::rect::Rect;
+    use sdl2::surface::Surface;
+
+    #[test]
+    fn software_renderer_test() {
+        let surface = Surface::new(160, 120, PixelFormatEnum::RGBA32).unwrap();
+        let mut canvas = surface.into_canvas().unwrap();
+        let texture_creator = canvas.texture_creator();
+        let mut crt = CrtFilter::new(&texture_creator);
+
+        crt.render(&mut canvas, |canvas| {
+            canvas.set_draw_color(Color::WHITE);
+            canvas.clear();
+            Ok(())
+        }).unwrap();
+
+        let pixels = canvas.read_pixels(Rect::new(0, 0, 160, 120), PixelFormatEnum::RGBA32).unwrap();
+        let red_at = |x: usize, y: usize| pixels[(y * 160 + x) * 4];
+
+        // even rows are left alone, odd rows are darkened by the scanlines
+        assert!(red_at(80, 60) > 200);
+        assert!(red_at(80, 61) > 100);
+        assert!(red_at(80, 61) < red_at(80, 60));
+        // the curved screen does not reach the corners
+        assert_eq!(red_at(0, 0), 0);
+    }
+
+    #[test]
+    fn empty_output_test() {
+        let surface = Surface::new(0, 0, PixelFormatEnum::RGBA32).unwrap();
+        let mut canvas = surface.into_canvas().unwrap();
+        let texture_creator = canvas.texture_creator();
+        let mut crt = CrtFilter::new(&texture_creator);
+
+        for _ in 0..2 {
+            crt.render(&mut canvas, |_| Ok(())).unwrap();
+        }
+    }
+
+    #[test]
+    fn disabled_filter_draws_directly_test() {
+        let surface = Surface::new(32, 32, PixelFormatEnum::RGBA32).unwrap();
+        let mut canvas = surface.into_canvas().unwrap();
+        let texture_creator = canvas.texture_creator();
+        let mut crt = CrtFilter::new(&texture_creator);
+        crt.toggle();
+
+        crt.render(&mut canvas, |canvas| {
+            canvas.set_draw_color(Color::WHITE);
+            canvas.clear();
+            Ok(())
+        }).unwrap();
+
+        let pixels = canvas.read_pixels(Rect::new(0, 0, 32, 32), PixelFormatEnum::RGBA32).unwrap();
+        assert_eq!(pixels[0], 255);
+    }
+
+    #[test]
+    fn logical_size_test() {
+        // 800x600 logical in a 500x300 window is pillarboxed to 400x300 at x 50
+        let surface = Surface::new(500, 300, PixelFormatEnum::RGBA32).unwrap();
+        let mut canvas = surface.into_canvas().unwrap();
+        canvas.set_logical_size(800, 600).unwrap();
+        let texture_creator = canvas.texture_creator();
+        let mut crt = CrtFilter::new(&texture_creator);
+        crt.settings.curvature = 0.0;
+        crt.settings.vignette = 0.0;
+        crt.settings.scanlines = 0.0;
+
+        crt.render(&mut canvas, |canvas| {
+            canvas.set_draw_color(Color::WHITE);
+            canvas.clear();
+            Ok(())
+        }).unwrap();
+
+        // read_pixels works in logical coordinates, go through the surface to see the bars
+        let surface = canvas.into_surface();
+        let pixels = surface.without_lock().unwrap();
+        let red_at = |x: usize, y: usize| pixels[y * surface.pitch() as usize + x * 4];
+        assert_eq!(red_at(10, 10), 0);
+        assert!(red_at(60, 10) > 200);
+        assert!(red_at(440, 10) > 200);
+        assert_eq!(red_at(490, 10), 0);
+    }
+}
@@ -0,0 +1,452 @@
+#![allow(dead_code)]
+
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::net::ToSocketAddrs;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use sdl2::rect::FPoint;
+
+use crate::net::{Connection, ConnectionState, MatchSetup, Message, NetworkPaddleController, PaddleInput};
+use crate::screens::{NetworkRole, Screen, ScreenFlow};
+use crate::{GameRng, PangGameState, Side};
+
+// both sides step the game at this fixed rate, whatever their frame rate
+pub const TICK: Duration = Duration::from_micros(16_667);
+// time beyond this that could not be simulated yet is dropped instead of caught up on
+const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+const CHECKSUM_HISTORY: usize = 600;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RollbackConfig {
+    // ticks between reading the local input and applying it, hides that much latency
+    // without any rollbacks at the cost of feeling sluggish
+    pub input_delay: u32,
+    // how far the game may run ahead of the last confirmed remote input before it waits
+    pub rollback_window: u32,
+}
+
+impl Default for RollbackConfig {
+    fn default() -> Self {
+        RollbackConfig {
+            input_delay: 2,
+            rollback_window: 8,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct RollbackStats {
+    pub rollbacks: u32,
+    pub resimulated_ticks: u32,
+    // updates spent waiting for the remote inputs to catch up
+    pub stalls: u32,
+}
+
+// the game right before `tick` was simulated
+#[derive(Copy, Clone)]
+struct SavedFrame {
+    tick: u32,
+    state: PangGameState,
+    screen: Screen,
+}
+
+/// Hash of everything that has to match on both sides, for spotting desyncs.
+pub fn checksum(state: &PangGameState, screen: &Screen) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let ball = &state.ball;
+    for value in [ball.pos.x(), ball.pos.y(), ball.velocity.x(), ball.velocity.y(), state.paddle_left.pos.y(), state.paddle_right.pos.y()] {
+        value.to_bits().hash(&mut hasher);
+    }
+    (state.score, state.rally, state.hit_stop_remaining).hash(&mut hasher);
+    let mut rng = state.rng;
+    rng.next_u64().hash(&mut hasher);
+    format!("{:?}", screen).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Peer to peer match where both sides simulate. Local input is applied right away (after
+/// `input_delay`), the remote input is guessed to stay what it was last and when the real
+/// one arrives different the game is put back to the saved state of that tick and
+/// simulated forward again. The host plays on the left.
+pub struct RollbackSession {
+    pub connection: Connection,
+    pub config: RollbackConfig,
+    pub stats: RollbackStats,
+    local_side: Side,
+    // read by the paddle controllers, set before every tick
+    left_input: Rc<Cell<PaddleInput>>,
+    right_input: Rc<Cell<PaddleInput>>,
+    started: bool,
+    // the next tick to simulate
+    tick: u32,
+    local_inputs: BTreeMap<u32, PaddleInput>,
+    remote_inputs: BTreeMap<u32, PaddleInput>,
+    // the remote input each simulated tick ran with, real or guessed
+    used_remote_inputs: BTreeMap<u32, PaddleInput>,
+    // every remote input before this tick has arrived
+    confirmed: u32,
+    // every local input before this tick has arrived on the other side
+    acknowledged: u32,
+    saved: VecDeque<SavedFrame>,
+    checksums: VecDeque<(u32, u64)>,
+    accumulator: Duration,
+    last_send: Option<Instant>,
+}
+
+impl RollbackSession {
+    pub fn host<A: ToSocketAddrs>(bind_addr: A, flow: &mut ScreenFlow, config: RollbackConfig) -> Result<RollbackSession, String> {
+        let connection = Connection::host(bind_addr, MatchSetup::capture(flow))?;
+        Ok(RollbackSession::new(connection, Side::Left, flow, config))
+    }
+
+    pub fn join<A: ToSocketAddrs>(host_addr: A, flow: &mut ScreenFlow, config: RollbackConfig) -> Result<RollbackSession, String> {
+        let connection = Connection::join(host_addr)?;
+        Ok(RollbackSession::new(connection, Side::Right, flow, config))
+    }
+
+    fn new(connection: Connection, local_side: Side, flow: &mut ScreenFlow, config: RollbackConfig) -> RollbackSession {
+        let (left, left_input) = NetworkPaddleController::new();
+        let (right, right_input) = NetworkPaddleController::new();
+        flow.start_network_match(NetworkRole::Peer, Box::new(left), Box::new(right));
+        RollbackSession {
+            connection,
+            config,
+            stats: RollbackStats::default(),
+            local_side,
+            left_input,
+            right_input,
+            started: false,
+            tick: 0,
+            local_inputs: BTreeMap::new(),
+            remote_inputs: BTreeMap::new(),
+            used_remote_inputs: BTreeMap::new(),
+            confirmed: 0,
+            acknowledged: 0,
+            saved: VecDeque::new(),
+            checksums: VecDeque::with_capacity(CHECKSUM_HISTORY),
+            accumulator: Duration::ZERO,
+            last_send: None,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(self.connection.state, ConnectionState::Disconnected(_))
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    // ticks before this one are simulated with the real inputs of both sides
+    pub fn confirmed_tick(&self) -> u32 {
+        self.confirmed.min(self.tick)
+    }
+
+    /// Checksum of the game right before `tick`, once it can no longer be rolled back.
+    pub fn checksum_at(&self, tick: u32) -> Option<u64> {
+        self.checksums.iter().find(|(checksum_tick, _)| *checksum_tick == tick).map(|(_, checksum)| *checksum)
+    }
+
+    // both sides start from the same state, setup and seed, the first ticks run without input
+    fn start(&mut self, flow: &mut ScreenFlow) {
+        self.started = true;
+        if let Some(setup) = &self.connection.setup {
+            setup.apply(flow);
+        }
+        flow.state.rng = GameRng::new(self.connection.seed);
+        flow.change_screen(Screen::ServeCountdown { remaining: Duration::ZERO, velocity: FPoint::new(0.0, 0.0) });
+
+        for tick in 0..self.config.input_delay {
+            self.local_inputs.insert(tick, PaddleInput::default());
+            self.remote_inputs.insert(tick, PaddleInput::default());
+        }
+        self.confirmed = self.config.input_delay;
+    }
+
+    // the last remote input known at or before `tick`
+    fn remote_input(&self, tick: u32) -> PaddleInput {
+        self.remote_inputs.range(..=tick).next_back().map(|(_, input)| *input).unwrap_or_default()
+    }
+
+    fn simulate(&mut self, flow: &mut ScreenFlow) -> Result<(), String> {
+        let local = self.local_inputs.get(&self.tick).copied().unwrap_or_default();
+        let remote = self.remote_input(self.tick);
+        self.used_remote_inputs.insert(self.tick, remote);
+        // the host is always left and the client right
+        let (left, right) = if self.local_side == Side::Left { (local, remote) } else { (remote, local) };
+        self.left_input.set(left);
+        self.right_input.set(right);
+
+        self.saved.push_back(SavedFrame { tick: self.tick, state: flow.state, screen: flow.screen });
+        flow.step(TICK)?;
+        self.tick += 1;
+        Ok(())
+    }
+
+    // puts the game back to right before `from` and plays it forward to where it was
+    fn roll_back(&mut self, flow: &mut ScreenFlow, from: u32) -> Result<(), String> {
+        let index = match self.saved.front() {
+            Some(first) if from >= first.tick && from < self.tick => (from - first.tick) as usize,
+            _ => return Ok(()),
+        };
+        let frame = self.saved[index];
+        self.saved.truncate(index);
+        flow.state = frame.state;
+        flow.screen = frame.screen;
+
+        // the replayed ticks already made their sounds and particles the first time
+        let particles = flow.controller.particles.take();
+        let events = flow.controller.events.len();
+        let target = self.tick;
+        self.tick = from;
+        let mut result = Ok(());
+        while self.tick < target && result.is_ok() {
+            result = self.simulate(flow);
+        }
+        flow.controller.events.truncate(events);
+        flow.controller.particles = particles;
+
+        self.stats.rollbacks += 1;
+        self.stats.resimulated_ticks += target - from;
+        result
+    }
+
+    // drops what can no longer be rolled back to, remembering its checksum
+    fn forget_confirmed(&mut self) {
+        let final_tick = self.confirmed_tick();
+        while let Some(frame) = self.saved.front() {
+            if frame.tick >= final_tick {
+                break;
+            }
+            if self.checksums.len() == CHECKSUM_HISTORY {
+                self.checksums.pop_front();
+            }
+            self.checksums.push_back((frame.tick, checksum(&frame.state, &frame.screen)));
+            self.saved.pop_front();
+        }
+
+        // prediction needs the last confirmed remote input, resending the unacknowledged local ones
+        let keep_remote = final_tick.saturating_sub(1);
+        self.remote_inputs = self.remote_inputs.split_off(&keep_remote);
+        self.used_remote_inputs = self.used_remote_inputs.split_off(&final_tick);
+        self.local_inputs = self.local_inputs.split_off(&self.acknowledged.min(final_tick));
+    }
+
+    /// Exchanges inputs, rolls back if a guess was wrong and simulates the ticks `delta_t`
+    /// adds up to. Call once per frame.
+    pub fn update(&mut self, flow: &mut ScreenFlow, local_input: PaddleInput, delta_t: Duration) -> Result<(), String> {
+        if self.is_closed() {
+            return Ok(());
+        }
+        // the player left through the menus
+        if flow.network.is_none() || matches!(flow.screen, Screen::Disconnected { .. } | Screen::Title) {
+            self.connection.close();
+            return Ok(());
+        }
+
+        let mut rollback_from: Option<u32> = None;
+        for message in self.connection.poll() {
+            if let Message::Input { tick, input, ack } = message {
+                self.acknowledged = self.acknowledged.max(ack);
+                if tick < self.confirmed || self.remote_inputs.contains_key(&tick) {
+                    continue;
+                }
+                self.remote_inputs.insert(tick, input);
+                if self.used_remote_inputs.get(&tick).is_some_and(|used| *used != input) {
+                    rollback_from = Some(rollback_from.map_or(tick, |from| from.min(tick)));
+                }
+            }
+        }
+        while self.remote_inputs.contains_key(&self.confirmed) {
+            self.confirmed += 1;
+        }
+
+        if let ConnectionState::Disconnected(reason) = self.connection.state {
+            flow.change_screen(Screen::Disconnected { reason });
+            return Ok(());
+        }
+        if !self.started {
+            if !self.connection.is_connected() {
+                return Ok(());
+            }
+            self.start(flow);
+        }
+
+        if let Some(from) = rollback_from {
+            self.roll_back(flow, from)?;
+        }
+
+        let mut advanced = false;
+        self.accumulator = (self.accumulator + delta_t).min(MAX_CATCH_UP);
+        while self.accumulator >= TICK {
+            if self.tick >= self.confirmed + self.config.rollback_window {
+                self.stats.stalls += 1;
+                break;
+            }
+            self.local_inputs.insert(self.tick + self.config.input_delay, local_input);
+            self.simulate(flow)?;
+            self.accumulator -= TICK;
+            advanced = true;
+        }
+        self.forget_confirmed();
+
+        // every unacknowledged input goes out again, so a lost packet only costs a rollback
+        if advanced || self.last_send.is_none_or(|sent| sent.elapsed() >= TICK) {
+            self.last_send = Some(Instant::now());
+            let pending: Vec<(u32, PaddleInput)> = self.local_inputs.range(self.acknowledged..).map(|(tick, input)| (*tick, *input)).collect();
+            for (tick, input) in pending {
+                self.connection.send(&Message::Input { tick, input, ack: self.confirmed });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RollbackSession {
+    fn drop(&mut self) {
+        self.connection.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::arena::Arena;
+    use crate::net::{LinkConditions, MatchSetup, PaddleInput};
+    use crate::rollback::{RollbackConfig, RollbackSession, TICK};
+    use crate::screens::{Screen, ScreenFlow};
+    use crate::test_support::{test_flow_with, test_state};
+    use std::time::Duration;
+
+    // fast bounces at random angles and long paddles, so rallies last and rollbacks happen
+    fn test_flow() -> ScreenFlow {
+        let mut state = test_state();
+        let physics = &mut state.ball.physics;
+        physics.restitution_factor = 1.05;
+        physics.restitution_angle_variance = 0.5;
+        physics.serve_velocity = 600.0;
+        state.paddle_left.set_length(200.0);
+        state.paddle_right.set_length(200.0);
+        test_flow_with(state)
+    }
+
+    // the players keep changing direction so the guessed remote input is wrong every now and then
+    fn scripted_input(tick: u32, period: u32) -> PaddleInput {
+        PaddleInput { up: (tick / period).is_multiple_of(2), down: !(tick / period).is_multiple_of(2) }
+    }
+
+    #[test]
+    fn rollback_under_latency_and_loss_test() {
+        let conditions = LinkConditions { latency: Duration::from_millis(20), loss: 0.2 };
+        let config = RollbackConfig { input_delay: 2, rollback_window: 12 };
+        let mut host_flow = test_flow();
+        let mut client_flow = test_flow();
+        let mut host = RollbackSession::host("127.0.0.1:0", &mut host_flow, config).unwrap();
+        let host_addr = host.connection.local_addr().unwrap();
+        let mut client = RollbackSession::join(host_addr, &mut client_flow, config).unwrap();
+        host.connection.conditions = conditions;
+        client.connection.conditions = conditions;
+
+        // the game runs a few times faster than real time to keep the test short
+        for _ in 0..3000 {
+            if host.tick() >= 400 && client.tick() >= 400 {
+                break;
+            }
+            let host_input = scripted_input(host.tick(), 17);
+            let client_input = scripted_input(client.tick(), 29);
+            host.update(&mut host_flow, host_input, TICK).unwrap();
+            client.update(&mut client_flow, client_input, TICK).unwrap();
+            std::thread::sleep(Duration::from_millis(4));
+        }
+        assert!(host.tick() >= 400 && client.tick() >= 400);
+
+        // stop simulating until the inputs of both sides are through
+        for _ in 0..500 {
+            if host.confirmed_tick() >= 390 && client.confirmed_tick() >= 390 {
+                break;
+            }
+            host.update(&mut host_flow, PaddleInput::default(), Duration::ZERO).unwrap();
+            client.update(&mut client_flow, PaddleInput::default(), Duration::ZERO).unwrap();
+            std::thread::sleep(Duration::from_millis(4));
+        }
+
+        assert!(host.stats.rollbacks + client.stats.rollbacks > 0);
+        assert!(matches!(host_flow.screen, Screen::Rally | Screen::PointScored { .. } | Screen::ServeCountdown { .. }));
+        for tick in (50..390).step_by(10) {
+            let host_checksum = host.checksum_at(tick);
+            assert!(host_checksum.is_some());
+            assert_eq!(host_checksum, client.checksum_at(tick), "desync at tick {}", tick);
+        }
+    }
+
+    #[test]
+    fn waits_for_remote_input_test() {
+        let config = RollbackConfig { input_delay: 1, rollback_window: 4 };
+        let mut host_flow = test_flow();
+        let mut client_flow = test_flow();
+        let mut host = RollbackSession::host("127.0.0.1:0", &mut host_flow, config).unwrap();
+        let host_addr = host.connection.local_addr().unwrap();
+        let mut client = RollbackSession::join(host_addr, &mut client_flow, config).unwrap();
+
+        for _ in 0..100 {
+            host.update(&mut host_flow, PaddleInput::default(), Duration::ZERO).unwrap();
+            client.update(&mut client_flow, PaddleInput::default(), Duration::ZERO).unwrap();
+            if host.connection.is_connected() && client.connection.is_connected() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert!(matches!(client_flow.screen, Screen::ServeCountdown { .. }));
+
+        // with the client silent the host only gets input delay plus rollback window ahead
+        host.update(&mut host_flow, PaddleInput::default(), TICK * 10).unwrap();
+        assert_eq!(host.tick(), 5);
+        assert_eq!(host.confirmed_tick(), 1);
+        assert!(host.stats.stalls > 0);
+    }
+
+    #[test]
+    fn plays_with_host_setup_test() {
+        let config = RollbackConfig { input_delay: 2, rollback_window: 12 };
+        let mut host_flow = test_flow();
+        host_flow.controller.arena = Some(Arena::parse("obstacle = circle 400 150 30\nobstacle = rect 390 400 20 60 moves 0 100 1.7").unwrap());
+        let mut client_flow = test_flow();
+        client_flow.controller.arena = Some(Arena::parse("obstacle = circle 400 300 40").unwrap());
+        client_flow.points_to_win = 2;
+        client_flow.configure(|state| {
+            state.ball.size = 12;
+            state.ball.physics.serve_velocity = 300.0;
+            state.paddle_left.set_length(60.0);
+            state.paddle_right.set_length(60.0);
+            state.paddle_right.movement_speed = 900.0;
+            state.speed_ramp = false;
+            state.hit_stop = false;
+        });
+        let mut host = RollbackSession::host("127.0.0.1:0", &mut host_flow, config).unwrap();
+        let host_addr = host.connection.local_addr().unwrap();
+        let mut client = RollbackSession::join(host_addr, &mut client_flow, config).unwrap();
+
+        for _ in 0..2000 {
+            if host.confirmed_tick() >= 200 && client.confirmed_tick() >= 200 {
+                break;
+            }
+            let host_input = scripted_input(host.tick(), 13);
+            let client_input = scripted_input(client.tick(), 23);
+            host.update(&mut host_flow, host_input, TICK).unwrap();
+            client.update(&mut client_flow, client_input, TICK).unwrap();
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        // the client dropped its own options for the host's
+        assert_eq!(MatchSetup::capture(&client_flow), MatchSetup::capture(&host_flow));
+        assert_eq!(client_flow.state.paddle_right.length(), 200.0);
+        for tick in (20..190).step_by(10) {
+            let host_checksum = host.checksum_at(tick);
+            assert!(host_checksum.is_some());
+            assert_eq!(host_checksum, client.checksum_at(tick), "desync at tick {}", tick);
+        }
+    }
+}
@@ -0,0 +1,757 @@
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::FPoint;
+use sdl2::render::{BlendMode, Canvas, RenderTarget};
+
+use crate::draw_primitives::draw_primitives::{draw_text, text_size, TextAlign};
+use crate::ui::Ui;
+use crate::{GameController, PaddleAIController, PaddleController, PangGameController, PangGameState, PlayerPaddleController, Side};
+
+const SERVE_COUNTDOWN: Duration = Duration::from_secs(3);
+const POINT_SCORED_DELAY: Duration = Duration::from_millis(1500);
+pub const DEFAULT_POINTS_TO_WIN: u32 = 5;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GameMode {
+    PlayerVsAi,
+    PlayerVsPlayer,
+    AiVsAi,
+    // paddles on all four walls
+    FourWayVsAi,
+    FourWayAi,
+    // alone against a solid right wall, endless
+    WallPractice,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 6] = [GameMode::PlayerVsAi, GameMode::PlayerVsPlayer, GameMode::AiVsAi, GameMode::FourWayVsAi, GameMode::FourWayAi, GameMode::WallPractice];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GameMode::PlayerVsAi => "PLAYER VS CPU",
+            GameMode::PlayerVsPlayer => "PLAYER VS PLAYER",
+            GameMode::AiVsAi => "CPU VS CPU",
+            GameMode::FourWayVsAi => "PLAYER VS 3 CPU",
+            GameMode::FourWayAi => "4 CPU",
+            GameMode::WallPractice => "WALL PRACTICE",
+        }
+    }
+
+    // the left player uses W/S, the right one the arrow keys, left or right without a
+    // controller is walled off
+    pub fn controllers(&self) -> Vec<(Side, Box<dyn PaddleController>)> {
+        let left_player = || Box::new(PlayerPaddleController::new(Keycode::W, Keycode::S));
+        let right_player = || Box::new(PlayerPaddleController::new(Keycode::Up, Keycode::Down));
+        let ai = || Box::new(PaddleAIController::new());
+        match self {
+            GameMode::PlayerVsAi => vec![(Side::Left, left_player()), (Side::Right, ai())],
+            GameMode::PlayerVsPlayer => vec![(Side::Left, left_player()), (Side::Right, right_player())],
+            GameMode::AiVsAi => vec![(Side::Left, ai()), (Side::Right, ai())],
+            GameMode::FourWayVsAi => vec![(Side::Left, left_player()), (Side::Right, ai()), (Side::Top, ai()), (Side::Bottom, ai())],
+            GameMode::FourWayAi => vec![(Side::Left, ai()), (Side::Right, ai()), (Side::Top, ai()), (Side::Bottom, ai())],
+            GameMode::WallPractice => vec![(Side::Left, left_player())],
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum NetworkRole {
+    // runs the simulation and sends snapshots
+    Host,
+    // only sends its input and shows what the host sent
+    Client,
+    // both sides simulate and roll back on late input, the session steps the flow
+    Peer,
+    // watches a match streamed by another instance, sends nothing back
+    Spectator,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Screen {
+    // a CPU match plays behind the title as the attract mode
+    Title,
+    ModeSelect { selected: usize },
+    // the ball waits in the middle with the serve velocity put aside
+    ServeCountdown { remaining: Duration, velocity: FPoint },
+    Rally,
+    PointScored { scorer: Side, remaining: Duration },
+    MatchOver { winner: Side },
+    // drawn over the screen it paused, which is kept in `ScreenFlow::paused`
+    PauseMenu { selected: usize },
+    // reachable from the title and the pause menu, edits take effect right away
+    Options,
+    // network match waiting for the other side
+    Connecting,
+    Disconnected { reason: &'static str },
+}
+
+const PAUSE_MENU: [&str; 4] = ["RESUME", "OPTIONS", "RESTART", "QUIT TO TITLE"];
+
+/// Top level state machine the game runs in. Each screen gets enter/exit hooks, its own
+/// input handling and drawing, the match itself is played by `controller`.
+pub struct ScreenFlow {
+    pub screen: Screen,
+    pub state: PangGameState,
+    pub controller: PangGameController,
+    pub mode: GameMode,
+    pub points_to_win: u32,
+    // set while playing over the network
+    pub network: Option<NetworkRole>,
+    // the state new matches start from
+    initial_state: PangGameState,
+    paused: Option<Screen>,
+    // where leaving the options goes back to
+    options_return: Screen,
+    ui: Ui,
+    quit: bool,
+}
+
+impl ScreenFlow {
+    pub fn new(state: PangGameState, controller: PangGameController) -> ScreenFlow {
+        let mut flow = ScreenFlow {
+            screen: Screen::Title,
+            state,
+            controller,
+            mode: GameMode::AiVsAi,
+            points_to_win: DEFAULT_POINTS_TO_WIN,
+            network: None,
+            initial_state: state,
+            paused: None,
+            options_return: Screen::Title,
+            ui: Ui::new(FPoint::new(state.canvas.center().x() - 240.0, state.canvas.top() + 40.0), 480.0),
+            quit: false,
+        };
+        flow.ui.row_height = 30.0;
+        flow.ui.text_scale = 2.0;
+        flow.enter();
+        flow
+    }
+
+    pub fn wants_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Runs the exit hook of the current screen and the enter hook of `next`.
+    pub fn change_screen(&mut self, next: Screen) {
+        self.exit();
+        self.screen = next;
+        self.enter();
+    }
+
+    fn enter(&mut self) {
+        match self.screen {
+            Screen::Title => {
+                self.network = None;
+                self.start_match(GameMode::AiVsAi);
+                self.state.serve_ball();
+                self.ui.set_focus(0);
+                self.place_ui(self.title_menu_top());
+            }
+            Screen::ServeCountdown { .. } => {
+                self.state.serve_ball();
+                self.screen = Screen::ServeCountdown {
+                    remaining: SERVE_COUNTDOWN,
+                    velocity: self.state.ball.velocity,
+                };
+                self.state.ball.velocity = FPoint::new(0.0, 0.0);
+            }
+            Screen::PointScored { .. } => {
+                // point_scored already served, keep the ball still until the countdown
+                self.state.ball.velocity = FPoint::new(0.0, 0.0);
+                self.state.ball.trail.clear();
+            }
+            Screen::Options => {
+                self.ui.set_focus(0);
+                self.place_ui(self.state.canvas.top() + 40.0);
+            }
+            Screen::ModeSelect { .. } | Screen::PauseMenu { .. } => self.place_ui(self.state.canvas.center().y()),
+            Screen::Rally | Screen::MatchOver { .. } | Screen::Connecting | Screen::Disconnected { .. } => {}
+        }
+    }
+
+    // the menu widgets of the next screen start at `top`
+    fn place_ui(&mut self, top: f32) {
+        self.ui.position = FPoint::new(self.state.canvas.center().x() - self.ui.width / 2.0, top);
+        self.ui.color = self.controller.theme.hud;
+    }
+
+    // below the big title text
+    fn title_menu_top(&self) -> f32 {
+        self.state.canvas.top() + self.state.canvas.height() * 0.55
+    }
+
+    fn exit(&mut self) {
+        if let Screen::ServeCountdown { velocity, .. } = self.screen {
+            self.state.ball.velocity = velocity;
+        }
+    }
+
+    fn start_match(&mut self, mode: GameMode) {
+        self.mode = mode;
+        self.reset_match();
+        self.controller.paddle_controller_top = None;
+        self.controller.paddle_controller_bottom = None;
+        let controllers = mode.controllers();
+        for side in [Side::Left, Side::Right] {
+            if !controllers.iter().any(|(controlled, _)| *controlled == side) {
+                self.state.remove_paddle(side);
+            }
+        }
+        for (side, controller) in controllers {
+            self.state.add_paddle(side);
+            self.controller.set_paddle_controller(side, controller);
+        }
+    }
+
+    fn reset_match(&mut self) {
+        self.controller.take_events();
+        if let Some(particles) = self.controller.particles.as_mut() {
+            particles.clear();
+        }
+        self.state = self.initial_state;
+    }
+
+    /// Sets up a match against another instance and waits on the connecting screen until
+    /// the network code moves on to the serve countdown.
+    pub fn start_network_match(&mut self, role: NetworkRole, left: Box<dyn PaddleController>, right: Box<dyn PaddleController>) {
+        self.controller.paddle_controller_left = left;
+        self.controller.paddle_controller_right = right;
+        self.reset_match();
+        self.network = Some(role);
+        self.change_screen(Screen::Connecting);
+    }
+
+    /// Changes the running match and the state new matches start from, for settings that
+    /// outlast a single match.
+    pub fn configure(&mut self, change: impl Fn(&mut PangGameState)) {
+        change(&mut self.state);
+        change(&mut self.initial_state);
+    }
+
+    fn in_match(&self) -> bool {
+        matches!(self.screen, Screen::Connecting | Screen::ServeCountdown { .. } | Screen::Rally | Screen::PointScored { .. } | Screen::MatchOver { .. })
+    }
+
+    fn pause(&mut self) {
+        self.paused = Some(self.screen);
+        self.screen = Screen::PauseMenu { selected: 0 };
+        self.enter();
+    }
+
+    // back to the paused screen without running its hooks again
+    fn resume(&mut self) {
+        self.exit();
+        if let Some(screen) = self.paused.take() {
+            self.screen = screen;
+        }
+    }
+
+    fn open_options(&mut self) {
+        self.options_return = self.screen;
+        self.change_screen(Screen::Options);
+    }
+
+    // the screens below the options are left as they were, so no hooks on the way back,
+    // OPTIONS is the second entry of both menus that open them
+    fn close_options(&mut self) {
+        self.exit();
+        self.screen = match self.options_return {
+            Screen::PauseMenu { .. } => Screen::PauseMenu { selected: 1 },
+            screen => screen,
+        };
+        self.ui.set_focus(1);
+        match self.screen {
+            Screen::Title => self.place_ui(self.title_menu_top()),
+            _ => self.place_ui(self.state.canvas.center().y()),
+        }
+    }
+
+    // one frame of the title menu, the attract match plays behind it
+    fn title_ui(&mut self) {
+        let ui = &mut self.ui;
+        ui.begin();
+        let play = ui.button("PLAY");
+        let options = ui.button("OPTIONS");
+        let quit = ui.button("QUIT") || ui.back();
+        ui.end();
+
+        if play {
+            self.change_screen(Screen::ModeSelect { selected: 0 });
+        } else if options {
+            self.open_options();
+        } else if quit {
+            self.quit = true;
+        }
+    }
+
+    // the focused entry is kept in the screen, so it survives the options in between
+    fn mode_select_ui(&mut self, selected: usize) {
+        let ui = &mut self.ui;
+        ui.set_focus(selected);
+        ui.begin();
+        let mut chosen = None;
+        for mode in GameMode::ALL {
+            if ui.button(mode.label()) {
+                chosen = Some(mode);
+            }
+        }
+        let back = ui.button("BACK") || ui.back();
+        ui.end();
+
+        self.screen = Screen::ModeSelect { selected: ui.focus() };
+        if let Some(mode) = chosen {
+            self.start_match(mode);
+            self.change_screen(Screen::ServeCountdown { remaining: SERVE_COUNTDOWN, velocity: FPoint::new(0.0, 0.0) });
+        } else if back {
+            self.change_screen(Screen::Title);
+        }
+    }
+
+    fn pause_ui(&mut self, selected: usize) {
+        let ui = &mut self.ui;
+        ui.set_focus(selected);
+        ui.begin();
+        let mut chosen = None;
+        for (index, label) in PAUSE_MENU.iter().enumerate() {
+            if ui.button(label) {
+                chosen = Some(index);
+            }
+        }
+        let back = ui.back();
+        ui.end();
+
+        self.screen = Screen::PauseMenu { selected: ui.focus() };
+        match chosen {
+            Some(0) => self.resume(),
+            Some(1) => self.open_options(),
+            Some(2) => {
+                self.paused = None;
+                self.start_match(self.mode);
+                self.change_screen(Screen::ServeCountdown { remaining: SERVE_COUNTDOWN, velocity: FPoint::new(0.0, 0.0) });
+            }
+            Some(_) => {
+                self.paused = None;
+                self.change_screen(Screen::Title);
+            }
+            None if back => self.resume(),
+            None => {}
+        }
+    }
+
+    // one frame of the options widgets, everything is written back to the match and to the
+    // state new matches start from
+    fn options_ui(&mut self) {
+        let mut physics = self.state.ball.physics;
+        let mut paddle_speed = self.state.paddle_left.movement_speed;
+        let mut paddle_height = self.state.paddle_left.length();
+        let mut points_to_win = self.points_to_win as f32;
+        let mut speed_ramp = self.state.speed_ramp;
+        let mut hit_stop = self.state.hit_stop;
+        let effects = &mut self.controller.effects;
+        let ui = &mut self.ui;
+
+        ui.begin();
+        ui.label("OPTIONS");
+        ui.slider("SERVE SPEED", &mut physics.serve_velocity, 100.0, 800.0, 25.0);
+        ui.slider("MAX SPEED", &mut physics.max_velocity, 300.0, 2000.0, 50.0);
+        ui.slider("BOUNCE GAIN", &mut physics.restitution_factor, 1.0, 1.5, 0.05);
+        ui.toggle("SPEED RAMP", &mut speed_ramp);
+        ui.slider("BOUNCE ANGLE", &mut physics.restitution_angle_variance, 0.0, 1.0, 0.05);
+        ui.slider("GRAVITY", &mut physics.vertical_acc, -500.0, 500.0, 50.0);
+        ui.slider("PADDLE SPEED", &mut paddle_speed, 200.0, 1200.0, 50.0);
+        ui.slider("PADDLE SIZE", &mut paddle_height, 40.0, 240.0, 10.0);
+        ui.slider("POINTS TO WIN", &mut points_to_win, 1.0, 21.0, 1.0);
+        ui.toggle("SCREEN SHAKE", &mut effects.screen_shake);
+        ui.toggle("HIT STOP", &mut hit_stop);
+        let back = ui.button("BACK") || ui.back();
+        ui.end();
+
+        self.points_to_win = points_to_win as u32;
+        self.configure(|state| {
+            state.ball.physics = physics;
+            state.speed_ramp = speed_ramp;
+            state.hit_stop = hit_stop;
+            for side in Side::ALL {
+                if let Some(paddle) = state.paddle_mut(side) {
+                    paddle.movement_speed = paddle_speed;
+                    paddle.set_length(paddle_height);
+                }
+            }
+        });
+
+        if back {
+            self.close_options();
+        }
+    }
+
+    fn run_match(&mut self, delta_t: Duration) -> Result<(), String> {
+        self.state = self.controller.update(&self.state, std::time::Instant::now(), delta_t, Event::Unknown { timestamp: 0, type_: 0 })?;
+        Ok(())
+    }
+
+    pub fn update(&mut self, delta_t: Duration) -> Result<(), String> {
+        match self.network {
+            Some(NetworkRole::Client) | Some(NetworkRole::Peer) | Some(NetworkRole::Spectator) => Ok(()),
+            _ => self.step(delta_t),
+        }
+    }
+
+    /// Advances the current screen by `delta_t`, for network sessions that drive the flow
+    /// at their own pace.
+    pub fn step(&mut self, delta_t: Duration) -> Result<(), String> {
+        match self.screen {
+            Screen::Title => {
+                self.run_match(delta_t)?;
+                self.title_ui();
+            }
+            Screen::ModeSelect { selected } => {
+                self.run_match(delta_t)?;
+                self.mode_select_ui(selected);
+            }
+            Screen::ServeCountdown { remaining, velocity } => {
+                self.run_match(delta_t)?;
+                match remaining.checked_sub(delta_t) {
+                    Some(remaining) if !remaining.is_zero() => self.screen = Screen::ServeCountdown { remaining, velocity },
+                    _ => self.change_screen(Screen::Rally),
+                }
+            }
+            Screen::Rally => {
+                let score = self.state.score;
+                self.run_match(delta_t)?;
+                if let Some(scorer) = Side::ALL.into_iter().find(|side| self.state.score.get(*side) != score.get(*side)) {
+                    self.change_screen(Screen::PointScored { scorer, remaining: POINT_SCORED_DELAY });
+                }
+            }
+            Screen::PointScored { scorer, remaining } => {
+                self.run_match(delta_t)?;
+                self.state.ball.velocity = FPoint::new(0.0, 0.0);
+                match remaining.checked_sub(delta_t) {
+                    Some(remaining) if !remaining.is_zero() => self.screen = Screen::PointScored { scorer, remaining },
+                    _ => {
+                        let score = self.state.score;
+                        // practice against the wall goes on until it is left
+                        if self.state.wall.is_none() && Side::ALL.iter().any(|side| score.get(*side) >= self.points_to_win) {
+                            self.change_screen(Screen::MatchOver { winner: scorer });
+                        } else {
+                            self.change_screen(Screen::ServeCountdown { remaining: SERVE_COUNTDOWN, velocity: FPoint::new(0.0, 0.0) });
+                        }
+                    }
+                }
+            }
+            Screen::Options => {
+                // the attract match keeps playing behind the options opened from the title
+                if self.options_return == Screen::Title {
+                    self.run_match(delta_t)?;
+                }
+                self.options_ui();
+            }
+            Screen::PauseMenu { selected } => self.pause_ui(selected),
+            Screen::MatchOver { .. } | Screen::Connecting | Screen::Disconnected { .. } => {}
+        }
+        Ok(())
+    }
+
+    pub fn handle_event(&mut self, event: &Event) -> Result<(), String> {
+        let keycode = match event {
+            Event::KeyDown { keycode: Some(keycode), repeat: false, .. } => Some(*keycode),
+            // start on a game controller pauses like escape
+            Event::ControllerButtonDown { button: Button::Start, .. } => Some(Keycode::Escape),
+            _ => None,
+        };
+
+        // network matches cannot be paused, escape leaves
+        if self.network.is_some() && self.in_match() {
+            match keycode {
+                Some(Keycode::Escape) if self.network == Some(NetworkRole::Spectator) => self.change_screen(Screen::Disconnected { reason: "YOU STOPPED WATCHING" }),
+                Some(Keycode::Escape) => self.change_screen(Screen::Disconnected { reason: "YOU LEFT THE MATCH" }),
+                Some(Keycode::Return) | Some(Keycode::Space) if self.network == Some(NetworkRole::Host) && matches!(self.screen, Screen::MatchOver { .. }) => {
+                    self.reset_match();
+                    self.change_screen(Screen::ServeCountdown { remaining: SERVE_COUNTDOWN, velocity: FPoint::new(0.0, 0.0) });
+                }
+                _ if self.network == Some(NetworkRole::Host) => {
+                    self.state = self.controller.update(&self.state, std::time::Instant::now(), Duration::ZERO, event.clone())?;
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        match self.screen {
+            // menus are widgets, they react on the next frame
+            Screen::Title | Screen::ModeSelect { .. } | Screen::PauseMenu { .. } | Screen::Options => self.ui.handle_event(event),
+            Screen::ServeCountdown { .. } | Screen::Rally | Screen::PointScored { .. } => match keycode {
+                Some(Keycode::Escape) => self.pause(),
+                // everything else goes to the paddle controllers
+                _ => self.state = self.controller.update(&self.state, std::time::Instant::now(), Duration::ZERO, event.clone())?,
+            },
+            Screen::MatchOver { .. } => match keycode {
+                Some(Keycode::Return) | Some(Keycode::Space) => self.change_screen(Screen::ModeSelect { selected: 0 }),
+                Some(Keycode::Escape) => self.change_screen(Screen::Title),
+                _ => {}
+            },
+            Screen::Disconnected { .. } => {
+                if let Some(Keycode::Return) | Some(Keycode::Space) | Some(Keycode::Escape) = keycode {
+                    self.change_screen(Screen::Title);
+                }
+            }
+            Screen::Connecting => {}
+        }
+        Ok(())
+    }
+
+    pub fn draw<T: RenderTarget>(&self, canvas: &mut Canvas<T>) -> Result<(), String> {
+        self.controller.draw(&self.state, canvas)?;
+
+        let field = self.state.canvas;
+        let center = field.center();
+        let color = self.controller.theme.hud;
+
+        match self.screen {
+            Screen::Title => {
+                dim(canvas, self.controller.theme.background)?;
+                draw_text(canvas, "PENG", FPoint::new(center.x(), field.top() + field.height() * 0.25), 12.0, TextAlign::Center, color)?;
+                self.ui.draw(canvas)?;
+            }
+            Screen::ModeSelect { .. } => {
+                dim(canvas, self.controller.theme.background)?;
+                draw_text(canvas, "SELECT MODE", FPoint::new(center.x(), field.top() + field.height() * 0.25), 5.0, TextAlign::Center, color)?;
+                self.ui.draw(canvas)?;
+            }
+            Screen::ServeCountdown { remaining, .. } => {
+                let seconds = format!("{}", remaining.as_secs_f32().ceil() as u32);
+                draw_centered(canvas, &seconds, center, 10.0, color)?;
+            }
+            Screen::Rally => {}
+            Screen::PointScored { scorer, .. } => {
+                let label = match scorer {
+                    _ if self.state.wall == Some(scorer) => "MISS",
+                    Side::Left => "POINT LEFT",
+                    Side::Right => "POINT RIGHT",
+                    Side::Top => "POINT TOP",
+                    Side::Bottom => "POINT BOTTOM",
+                };
+                draw_centered(canvas, label, FPoint::new(center.x(), field.top() + field.height() * 0.3), 5.0, color)?;
+            }
+            Screen::MatchOver { winner } => {
+                dim(canvas, self.controller.theme.background)?;
+                let label = match winner {
+                    Side::Left => "LEFT WINS",
+                    Side::Right => "RIGHT WINS",
+                    Side::Top => "TOP WINS",
+                    Side::Bottom => "BOTTOM WINS",
+                };
+                draw_centered(canvas, label, FPoint::new(center.x(), field.top() + field.height() * 0.35), 7.0, color)?;
+                let scores: Vec<String> = self.state.sides().map(|side| self.state.score.get(side).to_string()).collect();
+                let score = scores.join(" : ");
+                draw_centered(canvas, &score, center, 5.0, color)?;
+                draw_centered(canvas, "ENTER PLAY AGAIN  ESC TITLE", FPoint::new(center.x(), field.top() + field.height() * 0.7), 2.0, color)?;
+            }
+            Screen::PauseMenu { .. } => {
+                dim(canvas, self.controller.theme.background)?;
+                draw_text(canvas, "PAUSED", FPoint::new(center.x(), field.top() + field.height() * 0.25), 6.0, TextAlign::Center, color)?;
+                self.ui.draw(canvas)?;
+            }
+            Screen::Options => {
+                dim(canvas, self.controller.theme.background)?;
+                self.ui.draw(canvas)?;
+            }
+            Screen::Connecting => {
+                dim(canvas, self.controller.theme.background)?;
+                let label = match self.network {
+                    Some(NetworkRole::Host) => "WAITING FOR PLAYER",
+                    Some(NetworkRole::Spectator) => "WAITING FOR STREAM",
+                    _ => "CONNECTING",
+                };
+                draw_centered(canvas, label, center, 4.0, color)?;
+                draw_centered(canvas, "ESC CANCEL", FPoint::new(center.x(), field.top() + field.height() * 0.7), 2.0, color)?;
+            }
+            Screen::Disconnected { reason } => {
+                dim(canvas, self.controller.theme.background)?;
+                draw_centered(canvas, "DISCONNECTED", FPoint::new(center.x(), field.top() + field.height() * 0.35), 6.0, color)?;
+                draw_centered(canvas, reason, center, 3.0, color)?;
+                draw_centered(canvas, "PRESS ENTER", FPoint::new(center.x(), field.top() + field.height() * 0.7), 2.0, color)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// darkens the game behind an overlay screen with the theme background
+fn dim<T: RenderTarget>(canvas: &mut Canvas<T>, background: Color) -> Result<(), String> {
+    let blend_mode = canvas.blend_mode();
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(background.r, background.g, background.b, 180));
+    let result = canvas.fill_rect(None);
+    canvas.set_blend_mode(blend_mode);
+    result
+}
+
+fn draw_centered<T: RenderTarget>(canvas: &mut Canvas<T>, text: &str, center: FPoint, scale: f32, color: Color) -> Result<(), String> {
+    let (_, height) = text_size(text, scale);
+    draw_text(canvas, text, FPoint::new(center.x(), center.y() - height / 2.0), scale, TextAlign::Center, color)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::screens::{GameMode, Screen, ScreenFlow};
+    use crate::test_support::{key, test_flow};
+    use crate::Side;
+    use sdl2::controller::Button;
+    use sdl2::event::Event;
+    use sdl2::keyboard::Keycode;
+    use sdl2::rect::FPoint;
+    use std::time::Duration;
+
+    // menus only react on the frame after the event
+    fn press(flow: &mut ScreenFlow, event: Event) {
+        flow.handle_event(&event).unwrap();
+        flow.update(Duration::ZERO).unwrap();
+    }
+
+    #[test]
+    fn menu_to_countdown_test() {
+        let mut flow = test_flow();
+        assert_eq!(flow.screen, Screen::Title);
+        // attract mode serves right away
+        assert!(flow.state.ball.velocity.x() != 0.0);
+
+        press(&mut flow, key(Keycode::Return));
+        press(&mut flow, key(Keycode::Down));
+        assert_eq!(flow.screen, Screen::ModeSelect { selected: 1 });
+        press(&mut flow, key(Keycode::Return));
+        assert_eq!(flow.mode, GameMode::PlayerVsPlayer);
+        assert!(matches!(flow.screen, Screen::ServeCountdown { .. }));
+
+        // the ball is held during the countdown and released when the rally starts
+        flow.update(Duration::from_secs(1)).unwrap();
+        assert_eq!(flow.state.ball.velocity, FPoint::new(0.0, 0.0));
+        assert_eq!(flow.state.ball.pos, FPoint::new(400.0, 300.0));
+        flow.update(Duration::from_millis(2500)).unwrap();
+        assert_eq!(flow.screen, Screen::Rally);
+        assert!(flow.state.ball.velocity.x() != 0.0);
+    }
+
+    #[test]
+    fn point_and_match_over_test() {
+        let mut flow = test_flow();
+        flow.points_to_win = 1;
+        flow.change_screen(Screen::Rally);
+
+        // ball about to leave through the left wall
+        flow.state.ball.pos = FPoint::new(5.0, 100.0);
+        flow.state.ball.velocity = FPoint::new(-500.0, 0.0);
+        flow.update(Duration::from_millis(10)).unwrap();
+        assert!(matches!(flow.screen, Screen::PointScored { scorer: Side::Right, .. }));
+        assert_eq!(flow.state.ball.velocity, FPoint::new(0.0, 0.0));
+
+        flow.update(Duration::from_secs(2)).unwrap();
+        assert_eq!(flow.screen, Screen::MatchOver { winner: Side::Right });
+    }
+
+    #[test]
+    fn wall_practice_test() {
+        let mut flow = test_flow();
+        flow.points_to_win = 1;
+        press(&mut flow, key(Keycode::Return));
+        for _ in 0..GameMode::ALL.len() - 1 {
+            press(&mut flow, key(Keycode::Down));
+        }
+        press(&mut flow, key(Keycode::Return));
+        assert_eq!(flow.mode, GameMode::WallPractice);
+        assert!(flow.state.paddle(Side::Right).is_none());
+
+        // a miss counts for the wall and the practice goes on
+        flow.change_screen(Screen::Rally);
+        flow.state.ball.pos = FPoint::new(5.0, 100.0);
+        flow.state.ball.velocity = FPoint::new(-500.0, 0.0);
+        flow.update(Duration::from_millis(10)).unwrap();
+        assert!(matches!(flow.screen, Screen::PointScored { scorer: Side::Right, .. }));
+        flow.update(Duration::from_secs(2)).unwrap();
+        assert!(matches!(flow.screen, Screen::ServeCountdown { .. }));
+
+        // the next match gets its paddle back
+        flow.change_screen(Screen::Title);
+        assert!(flow.state.paddle(Side::Right).is_some());
+    }
+
+    #[test]
+    fn options_test() {
+        let mut flow = test_flow();
+        flow.change_screen(Screen::Rally);
+        press(&mut flow, key(Keycode::Escape));
+        press(&mut flow, key(Keycode::Down));
+        press(&mut flow, key(Keycode::Return));
+        assert_eq!(flow.screen, Screen::Options);
+        flow.update(Duration::ZERO).unwrap();
+
+        // serve speed is the first slider
+        press(&mut flow, key(Keycode::Right));
+        assert_eq!(flow.state.ball.physics.serve_velocity, 275.0);
+
+        // back goes to the pause menu, which still resumes the rally
+        press(&mut flow, key(Keycode::Escape));
+        assert_eq!(flow.screen, Screen::PauseMenu { selected: 1 });
+        press(&mut flow, key(Keycode::Escape));
+        assert_eq!(flow.screen, Screen::Rally);
+
+        // new matches keep the edited physics
+        flow.change_screen(Screen::Title);
+        assert_eq!(flow.state.ball.physics.serve_velocity, 275.0);
+    }
+
+    #[test]
+    fn pause_menu_test() {
+        let mut flow = test_flow();
+        flow.change_screen(Screen::Rally);
+        flow.state.ball.velocity = FPoint::new(100.0, 0.0);
+
+        flow.handle_event(&key(Keycode::Escape)).unwrap();
+        assert_eq!(flow.screen, Screen::PauseMenu { selected: 0 });
+        let before = flow.state.ball.pos;
+        flow.update(Duration::from_secs(1)).unwrap();
+        assert_eq!(flow.state.ball.pos, before);
+
+        // resuming does not serve again
+        press(&mut flow, key(Keycode::Return));
+        assert_eq!(flow.screen, Screen::Rally);
+        assert_eq!(flow.state.ball.velocity, FPoint::new(100.0, 0.0));
+
+        press(&mut flow, key(Keycode::Escape));
+        flow.handle_event(&key(Keycode::Up)).unwrap();
+        press(&mut flow, key(Keycode::Return));
+        assert_eq!(flow.screen, Screen::Title);
+
+        press(&mut flow, key(Keycode::Escape));
+        assert!(flow.wants_quit());
+    }
+
+    #[test]
+    fn gamepad_reaches_options_test() {
+        let mut flow = test_flow();
+        let button = |button| Event::ControllerButtonDown { timestamp: 0, which: 0, button };
+        flow.update(Duration::ZERO).unwrap();
+
+        press(&mut flow, button(Button::DPadDown));
+        press(&mut flow, button(Button::A));
+        assert_eq!(flow.screen, Screen::Options);
+        press(&mut flow, button(Button::B));
+        assert_eq!(flow.screen, Screen::Title);
+
+        // start pauses a match, the pause menu opens the options as well
+        press(&mut flow, button(Button::DPadUp));
+        press(&mut flow, button(Button::A));
+        press(&mut flow, button(Button::A));
+        assert!(matches!(flow.screen, Screen::ServeCountdown { .. }));
+        flow.handle_event(&button(Button::Start)).unwrap();
+        assert_eq!(flow.screen, Screen::PauseMenu { selected: 0 });
+        press(&mut flow, button(Button::DPadDown));
+        press(&mut flow, button(Button::A));
+        assert_eq!(flow.screen, Screen::Options);
+    }
+}
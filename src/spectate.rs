@@ -0,0 +1,267 @@
+#![allow(dead_code)]
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use crate::net::{MatchSetup, Message, NetworkPaddleController, Snapshot};
+use crate::screens::{NetworkRole, Screen, ScreenFlow};
+
+pub const DEFAULT_SPECTATE_PORT: u16 = 7778;
+const BROADCAST_INTERVAL: Duration = Duration::from_micros(16_667);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// a viewer this far behind is too slow to keep up and gets dropped
+const MAX_PENDING: usize = 64 * 1024;
+
+// frames on the stream are a little endian u16 length followed by an encoded `Message`
+fn frame(message: &Message) -> Vec<u8> {
+    let bytes = message.encode();
+    let mut frame = (bytes.len() as u16).to_le_bytes().to_vec();
+    frame.extend_from_slice(&bytes);
+    frame
+}
+
+struct Viewer {
+    stream: TcpStream,
+    // bytes the socket did not take yet
+    pending: Vec<u8>,
+}
+
+impl Viewer {
+    // false once the viewer is gone or too far behind
+    fn flush(&mut self) -> bool {
+        while !self.pending.is_empty() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => return false,
+                Ok(written) => {
+                    self.pending.drain(..written);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => return false,
+            }
+        }
+        self.pending.len() <= MAX_PENDING
+    }
+}
+
+/// Streams the running match to any number of viewers over TCP, see `SpectatorClient`.
+pub struct SpectatorServer {
+    listener: TcpListener,
+    viewers: Vec<Viewer>,
+    tick: u32,
+    last_broadcast: Option<Instant>,
+}
+
+impl SpectatorServer {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<SpectatorServer, String> {
+        let listener = TcpListener::bind(addr).map_err(|e| e.to_string())?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(SpectatorServer {
+            listener,
+            viewers: Vec::new(),
+            tick: 0,
+            last_broadcast: None,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<std::net::SocketAddr, String> {
+        self.listener.local_addr().map_err(|e| e.to_string())
+    }
+
+    pub fn viewer_count(&self) -> usize {
+        self.viewers.len()
+    }
+
+    /// Takes new viewers and sends them all the current state, at most 60 times a second.
+    pub fn broadcast(&mut self, flow: &ScreenFlow) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                let _ = stream.set_nodelay(true);
+                // new viewers get the sizes, rules and arena first, the snapshots only carry positions
+                let pending = frame(&Message::Welcome { seed: 0, setup: MatchSetup::capture(flow) });
+                self.viewers.push(Viewer { stream, pending });
+            }
+        }
+
+        if self.last_broadcast.is_some_and(|sent| sent.elapsed() < BROADCAST_INTERVAL) {
+            return;
+        }
+        self.last_broadcast = Some(Instant::now());
+        self.tick += 1;
+
+        let frame = frame(&Message::Snapshot(Snapshot::capture(self.tick, &flow.state, flow.screen)));
+        self.viewers.retain_mut(|viewer| {
+            viewer.pending.extend_from_slice(&frame);
+            viewer.flush()
+        });
+    }
+}
+
+/// Shows a match streamed by a `SpectatorServer`, the flow only draws what arrives.
+pub struct SpectatorClient {
+    stream: TcpStream,
+    received: Vec<u8>,
+    closed: bool,
+}
+
+impl SpectatorClient {
+    pub fn connect<A: ToSocketAddrs>(addr: A, flow: &mut ScreenFlow) -> Result<SpectatorClient, String> {
+        let addr = addr.to_socket_addrs().map_err(|e| e.to_string())?.next().ok_or("could not resolve the stream address")?;
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).map_err(|e| e.to_string())?;
+        stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        // nothing is simulated, the controllers are never asked
+        let (left, _) = NetworkPaddleController::new();
+        let (right, _) = NetworkPaddleController::new();
+        flow.start_network_match(NetworkRole::Spectator, Box::new(left), Box::new(right));
+        Ok(SpectatorClient {
+            stream,
+            received: Vec::new(),
+            closed: false,
+        })
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    // splits complete frames off the front of `received`, undecodable ones are skipped
+    fn take_frames(&mut self) -> Vec<Message> {
+        let mut messages = Vec::new();
+        let mut start = 0;
+        while self.received.len() - start >= 2 {
+            let length = u16::from_le_bytes([self.received[start], self.received[start + 1]]) as usize;
+            if self.received.len() - start - 2 < length {
+                break;
+            }
+            if let Ok(message) = Message::decode(&self.received[start + 2..start + 2 + length]) {
+                messages.push(message);
+            }
+            start += 2 + length;
+        }
+        self.received.drain(..start);
+        messages
+    }
+
+    /// Reads what arrived and shows the newest state, call once per frame.
+    pub fn update(&mut self, flow: &mut ScreenFlow) {
+        if self.closed {
+            return;
+        }
+        if flow.network.is_none() || matches!(flow.screen, Screen::Disconnected { .. } | Screen::Title) {
+            self.closed = true;
+            return;
+        }
+
+        let mut ended = false;
+        let mut buffer = [0u8; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    ended = true;
+                    break;
+                }
+                Ok(length) => self.received.extend_from_slice(&buffer[..length]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    ended = true;
+                    break;
+                }
+            }
+        }
+
+        for message in self.take_frames() {
+            match message {
+                Message::Welcome { setup, .. } => setup.apply(flow),
+                Message::Snapshot(snapshot) => snapshot.show(flow),
+                _ => {}
+            }
+        }
+        if ended {
+            self.closed = true;
+            flow.change_screen(Screen::Disconnected { reason: "THE STREAM ENDED" });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::arena::Arena;
+    use crate::screens::Screen;
+    use crate::spectate::{SpectatorClient, SpectatorServer};
+    use crate::test_support::test_flow;
+    use crate::Score;
+    use std::time::Duration;
+
+    #[test]
+    fn stream_to_viewer_test() {
+        let mut server = SpectatorServer::bind("127.0.0.1:0").unwrap();
+        let mut match_flow = test_flow();
+        let mut viewer_flow = test_flow();
+        let mut viewer = SpectatorClient::connect(server.local_addr().unwrap(), &mut viewer_flow).unwrap();
+        assert_eq!(viewer_flow.screen, Screen::Connecting);
+
+        match_flow.state.score = Score { left: 2, right: 3, top: 0, bottom: 0 };
+        match_flow.configure(|state| state.paddle_left.set_length(60.0));
+        match_flow.controller.arena = Some(Arena::parse("obstacle = circle 400 300 40").unwrap());
+        for _ in 0..100 {
+            match_flow.update(Duration::from_millis(17)).unwrap();
+            server.broadcast(&match_flow);
+            viewer.update(&mut viewer_flow);
+            if viewer_flow.state.ball.pos == match_flow.state.ball.pos && viewer_flow.screen == Screen::Rally {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(17));
+        }
+        assert_eq!(server.viewer_count(), 1);
+        assert_eq!(viewer_flow.state.score, Score { left: 2, right: 3, top: 0, bottom: 0 });
+        assert_eq!(viewer_flow.state.ball.pos, match_flow.state.ball.pos);
+        assert_eq!(viewer_flow.state.paddle_left.length(), 60.0);
+        assert_eq!(viewer_flow.controller.arena, match_flow.controller.arena);
+        // the attract match behind the title is shown as a plain rally
+        assert_eq!(viewer_flow.screen, Screen::Rally);
+
+        // the viewer never simulates on its own
+        let pos = viewer_flow.state.ball.pos;
+        viewer_flow.update(Duration::from_millis(100)).unwrap();
+        assert_eq!(viewer_flow.state.ball.pos, pos);
+
+        drop(server);
+        for _ in 0..100 {
+            viewer.update(&mut viewer_flow);
+            if viewer.is_closed() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(viewer_flow.screen, Screen::Disconnected { reason: "THE STREAM ENDED" });
+    }
+
+    #[test]
+    fn viewer_leaving_test() {
+        let mut server = SpectatorServer::bind("127.0.0.1:0").unwrap();
+        let flow = test_flow();
+        let mut viewer_flow = test_flow();
+        let viewer = SpectatorClient::connect(server.local_addr().unwrap(), &mut viewer_flow).unwrap();
+
+        for _ in 0..50 {
+            server.broadcast(&flow);
+            if server.viewer_count() == 1 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(server.viewer_count(), 1);
+
+        // writing to a closed socket fails within a few frames
+        drop(viewer);
+        for _ in 0..100 {
+            server.broadcast(&flow);
+            if server.viewer_count() == 0 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(server.viewer_count(), 0);
+    }
+}
@@ -0,0 +1,356 @@
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use sdl2::pixels::Color;
+use sdl2::rect::FPoint;
+
+use crate::draw_primitives::draw_primitives::Sprite;
+use crate::gradient::{ColorStop, Gradient};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BallShape {
+    Dots,
+    Polygon { edges: u32, filled: bool },
+    Circle,
+    // uses `Theme::ball_sprite`, or a built-in sprite in the ball color when the theme has none
+    Sprite,
+}
+
+impl BallShape {
+    // cycles through the shapes, used to switch the ball style at runtime
+    pub fn next(&self) -> BallShape {
+        match self {
+            BallShape::Dots => BallShape::Polygon { edges: 7, filled: false },
+            BallShape::Polygon { .. } => BallShape::Circle,
+            BallShape::Circle => BallShape::Sprite,
+            BallShape::Sprite => BallShape::Dots,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PaddleStyle {
+    Outline,
+    Filled,
+    Rounded { radius: f32 },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Theme {
+    pub name: String,
+    pub background: Color,
+    pub background_gradient: Option<Gradient>,
+    pub ball: Color,
+    pub paddle: Color,
+    pub hud: Color,
+    pub ball_shape: BallShape,
+    pub ball_sprite: Option<Sprite>,
+    pub ball_trail: bool,
+    pub paddle_style: PaddleStyle,
+    pub antialias: bool,
+    default_ball_sprite: SpriteCache,
+}
+
+// the sprite built from `ball` for sprite balls without `ball_sprite`, rebuilt when `ball`
+// changes. Themes that only differ in what is cached are equal.
+#[derive(Clone, Debug, Default)]
+struct SpriteCache(RefCell<Option<(Color, Rc<Sprite>)>>);
+
+impl PartialEq for SpriteCache {
+    fn eq(&self, _other: &SpriteCache) -> bool {
+        true
+    }
+}
+
+impl Theme {
+    // the look the game had before themes existed
+    pub fn default_theme() -> Theme {
+        Theme {
+            name: String::from("default"),
+            background: Color::BLACK,
+            background_gradient: Some(Gradient::two_color(45.0, Color::BLUE, Color::RED)),
+            ball: Color::GREEN,
+            paddle: Color::GREEN,
+            hud: Color::GREEN,
+            ball_shape: BallShape::Polygon { edges: 7, filled: false },
+            ball_sprite: None,
+            ball_trail: false,
+            paddle_style: PaddleStyle::Outline,
+            antialias: false,
+            default_ball_sprite: SpriteCache::default(),
+        }
+    }
+
+    pub fn classic() -> Theme {
+        Theme {
+            name: String::from("classic"),
+            background: Color::BLACK,
+            background_gradient: None,
+            ball: Color::WHITE,
+            paddle: Color::WHITE,
+            hud: Color::WHITE,
+            ball_shape: BallShape::Polygon { edges: 4, filled: true },
+            ball_sprite: None,
+            ball_trail: false,
+            paddle_style: PaddleStyle::Filled,
+            antialias: false,
+            default_ball_sprite: SpriteCache::default(),
+        }
+    }
+
+    pub fn neon() -> Theme {
+        Theme {
+            name: String::from("neon"),
+            background: Color::RGB(10, 0, 20),
+            background_gradient: Some(Gradient::radial(FPoint::new(0.5, 0.5), 0.8, vec![
+                ColorStop::new(0.0, Color::RGB(60, 0, 90)),
+                ColorStop::new(0.6, Color::RGB(20, 0, 45)),
+                ColorStop::new(1.0, Color::RGB(5, 0, 10)),
+            ])),
+            ball: Color::RGB(255, 40, 200),
+            paddle: Color::RGB(0, 240, 255),
+            hud: Color::RGB(255, 230, 0),
+            ball_shape: BallShape::Circle,
+            ball_sprite: None,
+            ball_trail: true,
+            paddle_style: PaddleStyle::Rounded { radius: 8.0 },
+            antialias: true,
+            default_ball_sprite: SpriteCache::default(),
+        }
+    }
+
+    pub fn high_contrast() -> Theme {
+        Theme {
+            name: String::from("high contrast"),
+            background: Color::BLACK,
+            background_gradient: None,
+            ball: Color::YELLOW,
+            paddle: Color::WHITE,
+            hud: Color::WHITE,
+            ball_shape: BallShape::Circle,
+            ball_sprite: None,
+            ball_trail: false,
+            paddle_style: PaddleStyle::Filled,
+            antialias: false,
+            default_ball_sprite: SpriteCache::default(),
+        }
+    }
+
+    pub fn default_ball_sprite(&self) -> Rc<Sprite> {
+        let mut cache = self.default_ball_sprite.0.borrow_mut();
+        match &*cache {
+            Some((color, sprite)) if *color == self.ball => sprite.clone(),
+            _ => {
+                let sprite = Rc::new(self.build_default_ball_sprite());
+                *cache = Some((self.ball, sprite.clone()));
+                sprite
+            }
+        }
+    }
+
+    fn build_default_ball_sprite(&self) -> Sprite {
+        let shade = Color::RGBA(self.ball.r / 2, self.ball.g / 2, self.ball.b / 2, self.ball.a);
+        Sprite::from_art(&[
+            "...####...",
+            ".########.",
+            "##++######",
+            "#++#######",
+            "#+########",
+            "#########-",
+            "########--",
+            ".######--.",
+            ".#####---.",
+            "...----...",
+        ], &[('#', self.ball), ('+', Color::WHITE), ('-', shade)])
+    }
+
+    pub fn builtin() -> Vec<Theme> {
+        vec![Theme::default_theme(), Theme::classic(), Theme::neon(), Theme::high_contrast()]
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Theme, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let base_dir = path.parent().unwrap_or(std::path::Path::new(""));
+        Theme::parse_relative_to(&text, base_dir).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Theme, String> {
+        Theme::parse_relative_to(text, std::path::Path::new(""))
+    }
+
+    /// Parses a theme from `key = value` lines. Lines starting with `#` and anything after
+    /// ` # ` are comments. Keys that are not present keep the values of the default theme.
+    /// Files referenced by the theme are looked up relative to `base_dir`.
+    pub fn parse_relative_to(text: &str, base_dir: &std::path::Path) -> Result<Theme, String> {
+        let mut theme = Theme::default_theme();
+        theme.name = String::from("custom");
+
+        for line in key_value_lines(text) {
+            let (line, key, value) = line?;
+            let error = |message: String| line_error(line, message);
+
+            match key {
+                "name" => theme.name = value.to_string(),
+                "background" => theme.background = parse_color(value).map_err(error)?,
+                "background_gradient" => theme.background_gradient = parse_gradient(value).map_err(error)?,
+                "ball" => theme.ball = parse_color(value).map_err(error)?,
+                "paddle" => theme.paddle = parse_color(value).map_err(error)?,
+                "hud" => theme.hud = parse_color(value).map_err(error)?,
+                "ball_shape" => theme.ball_shape = parse_ball_shape(value).map_err(error)?,
+                "ball_sprite" => theme.ball_sprite = Some(Sprite::load_bmp(&base_dir.join(value)).map_err(error)?),
+                "paddle_style" => theme.paddle_style = parse_paddle_style(value).map_err(error)?,
+                "ball_trail" => theme.ball_trail = value.parse::<bool>().map_err(|e| error(e.to_string()))?,
+                "antialias" => theme.antialias = value.parse::<bool>().map_err(|e| error(e.to_string()))?,
+                other => return Err(error(format!("unknown key `{}`", other))),
+            }
+        }
+        Ok(theme)
+    }
+}
+
+/// The `key = value` lines of a theme, song, level or arena file, with the key and value
+/// trimmed and the 1-based line number for error messages. Lines starting with `#`, anything
+/// after ` # ` and blank lines are skipped, a line without `=` is an error.
+pub fn key_value_lines(text: &str) -> impl Iterator<Item = Result<(usize, &str, &str), String>> {
+    text.lines().enumerate().filter_map(|(line_index, raw_line)| {
+        let line = match raw_line.find(" # ") {
+            Some(comment) => &raw_line[..comment],
+            None if raw_line.trim_start().starts_with('#') => "",
+            None => raw_line,
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        Some(match line.split_once('=') {
+            Some((key, value)) => Ok((line_index + 1, key.trim(), value.trim())),
+            None => Err(line_error(line_index + 1, format!("expected `key = value`, got `{}`", line))),
+        })
+    })
+}
+
+/// Prefixes a parse error with the line it was found on.
+pub fn line_error(line: usize, message: String) -> String {
+    format!("line {}: {}", line, message)
+}
+
+/// Parses `#RRGGBB` or `#RRGGBBAA`.
+pub fn parse_color(value: &str) -> Result<Color, String> {
+    let hex = value.strip_prefix('#').ok_or_else(|| format!("color `{}` must start with #", value))?;
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid color `{}`", value));
+    }
+
+    let channel = |index: usize| u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).unwrap();
+    let alpha = if hex.len() == 8 { channel(3) } else { 255 };
+    Ok(Color::RGBA(channel(0), channel(1), channel(2), alpha))
+}
+
+// `none`, `linear <angle> <color>...` or `radial <color>...`, stops are spread evenly
+fn parse_gradient(value: &str) -> Result<Option<Gradient>, String> {
+    let mut words = value.split_whitespace();
+    let kind = words.next().unwrap_or("none");
+    if kind == "none" {
+        return Ok(None);
+    }
+
+    let angle = if kind == "linear" {
+        let angle = words.next().ok_or("linear gradient needs an angle")?;
+        Some(angle.parse::<f32>().map_err(|e| format!("invalid angle `{}`: {}", angle, e))?)
+    } else {
+        None
+    };
+
+    let colors = words.map(parse_color).collect::<Result<Vec<Color>, String>>()?;
+    if colors.len() < 2 {
+        return Err(String::from("a gradient needs at least two colors"));
+    }
+    let stops = colors.iter().enumerate()
+        .map(|(i, color)| ColorStop::new(i as f32 / (colors.len() - 1) as f32, *color))
+        .collect();
+
+    match (kind, angle) {
+        ("linear", Some(angle)) => Ok(Some(Gradient::linear(angle, stops))),
+        ("radial", _) => Ok(Some(Gradient::radial(FPoint::new(0.5, 0.5), 0.75, stops))),
+        _ => Err(format!("unknown gradient kind `{}`", kind)),
+    }
+}
+
+fn parse_ball_shape(value: &str) -> Result<BallShape, String> {
+    let words: Vec<&str> = value.split_whitespace().collect();
+    match words.as_slice() {
+        ["dots"] => Ok(BallShape::Dots),
+        ["circle"] => Ok(BallShape::Circle),
+        ["sprite"] => Ok(BallShape::Sprite),
+        ["polygon", edges] | ["polygon", edges, "outline"] => Ok(BallShape::Polygon { edges: parse_edges(edges)?, filled: false }),
+        ["polygon", edges, "filled"] => Ok(BallShape::Polygon { edges: parse_edges(edges)?, filled: true }),
+        _ => Err(format!("invalid ball shape `{}`", value)),
+    }
+}
+
+fn parse_edges(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(edges) if edges >= 3 => Ok(edges),
+        _ => Err(format!("invalid edge count `{}`", value)),
+    }
+}
+
+fn parse_paddle_style(value: &str) -> Result<PaddleStyle, String> {
+    let words: Vec<&str> = value.split_whitespace().collect();
+    match words.as_slice() {
+        ["outline"] => Ok(PaddleStyle::Outline),
+        ["filled"] => Ok(PaddleStyle::Filled),
+        ["rounded", radius] => Ok(PaddleStyle::Rounded {
+            radius: radius.parse::<f32>().map_err(|e| format!("invalid radius `{}`: {}", radius, e))?,
+        }),
+        _ => Err(format!("invalid paddle style `{}`", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::theme::{parse_color, BallShape, PaddleStyle, Theme};
+    use sdl2::pixels::Color;
+
+    #[test]
+    fn parse_theme_test() {
+        let theme = Theme::parse("
+            # comments and blank lines are skipped
+            name = amber
+            background = #100800
+            background_gradient = linear 90 #000000 #302000 #000000
+            ball = #FFB000 # trailing comment
+            paddle = #ffb00080
+            ball_shape = polygon 6 filled
+            paddle_style = rounded 4
+            antialias = true
+        ").unwrap();
+
+        assert_eq!(theme.name, "amber");
+        assert_eq!(theme.background, Color::RGB(0x10, 0x08, 0x00));
+        assert_eq!(theme.background_gradient.unwrap().stops.len(), 3);
+        assert_eq!(theme.ball, Color::RGB(0xFF, 0xB0, 0x00));
+        assert_eq!(theme.paddle, Color::RGBA(0xFF, 0xB0, 0x00, 0x80));
+        assert_eq!(theme.hud, Theme::default_theme().hud);
+        assert_eq!(theme.ball_shape, BallShape::Polygon { edges: 6, filled: true });
+        assert_eq!(theme.paddle_style, PaddleStyle::Rounded { radius: 4.0 });
+        assert!(theme.antialias);
+    }
+
+    #[test]
+    fn parse_errors_test() {
+        assert!(parse_color("FFFFFF").is_err());
+        assert!(parse_color("#FFF").is_err());
+        assert_eq!(Theme::parse("ball = #00FF00\nsparkles = on").unwrap_err(), "line 2: unknown key `sparkles`");
+        assert!(Theme::parse("ball_shape = polygon 2").is_err());
+        assert!(Theme::parse("background_gradient = linear 45 #000000").is_err());
+    }
+
+    #[test]
+    fn example_theme_file_test() {
+        let theme = Theme::load(std::path::Path::new("themes/amber.theme")).unwrap();
+        assert_eq!(theme.name, "amber");
+    }
+}
@@ -1,109 +1,194 @@
 #![allow(dead_code)]
-#[allow(unused)]
-
-pub mod tick_controller {
-    pub struct TickController {
-        target_fps: u32,
-        frame_count: u64,
-        current_tick_actual: std::time::Instant,
-        scheduled_tick: std::time::Instant,
-        previous_tick_actual: std::time::Instant,
-        tick_interval: std::time::Duration,
-        last_fps_check : std::time::Instant,
-        paused: bool,
-    } 
-
-    // "Something is wrong with, the calculated fps is too high"
-    impl TickController {
-
-        pub fn pause(&mut self) {
-            self.paused = true;
-        }
 
-        pub fn resume(&mut self) {
-            self.paused = false;
+// time scales offered by slower()/faster()
+pub const TIME_SCALES: [f32; 7] = [0.1, 0.25, 0.5, 1.0, 1.5, 2.0, 4.0];
+// how far a single step advances the game, one frame at 60 fps regardless of the tick rate
+pub const STEP_DURATION: std::time::Duration = std::time::Duration::from_micros(16_667);
+
+pub struct TickController {
+    target_fps: u32,
+    frame_count: u64,
+    current_tick_actual: std::time::Instant,
+    scheduled_tick: std::time::Instant,
+    previous_tick_actual: std::time::Instant,
+    tick_interval: std::time::Duration,
+    last_fps_check : std::time::Instant,
+    paused: bool,
+    step_requested: bool,
+    time_scale: f32,
+    last_fps: f32,
+    skipped_frames: u64,
+    busy_duration: std::time::Duration,
+} 
+
+#[derive(Copy, Clone, Debug)]
+pub struct FrameStats {
+    pub target_fps: u32,
+    pub fps: f32,
+    pub skipped_frames: u64,
+    pub busy_duration: std::time::Duration,
+    pub tick_interval: std::time::Duration,
+    pub paused: bool,
+    pub time_scale: f32,
+}
+
+// "Something is wrong with, the calculated fps is too high"
+impl TickController {
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.step_requested = false;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.paused {
+            self.resume();
+        } else {
+            self.pause();
         }
+    }
 
-        pub fn from_target_fps(target_fps: u32) -> Self {
-            let current_tick = std::time::Instant::now();
-            let next_tick = current_tick;
-            let previous_tick = current_tick;
-            let tick_interval = std::time::Duration::from_secs_f32(1.0 / target_fps as f32);
-            let frame_count = 0;
-            let last_fps_check = std::time::Instant::now();
-
-            TickController {
-                target_fps,
-                frame_count,
-                current_tick_actual: current_tick,
-                scheduled_tick: next_tick,
-                previous_tick_actual: previous_tick,
-                tick_interval,
-                last_fps_check,
-                paused: false,
-            }
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Advances a paused game by `STEP_DURATION` on the next `game_delta`. Does nothing
+    /// while running.
+    pub fn step(&mut self) {
+        if self.paused {
+            self.step_requested = true;
         }
+    }
 
-        pub fn wait_for_next_tick(&mut self) {
-            self.frame_count += 1;
-            if self.last_fps_check.elapsed().as_secs_f32() > 1.0 {
-                println!("FPS: {}", self.fps_check());
-            }
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
 
-            let now: std::time::Instant = std::time::Instant::now();
-            let sleep_duration = self.scheduled_tick.saturating_duration_since(now);
-            let busy_duration = now.saturating_duration_since(self.current_tick_actual);
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
 
-            if sleep_duration.as_micros() > 0 {
-                while std::time::Instant::now() < self.scheduled_tick {
-                    ();
-                }
-                //std::thread::sleep(sleep_duration);
-            }
-            else {
-                let missed = now.saturating_duration_since(self.scheduled_tick);
-                //println!("Missed frame by: {}ms", missed.as_millis());
-            }
+    pub fn slower(&mut self) {
+        let slower = TIME_SCALES.iter().rev().find(|scale| **scale < self.time_scale);
+        self.time_scale = *slower.unwrap_or(&TIME_SCALES[0]);
+    }
+
+    pub fn faster(&mut self) {
+        let faster = TIME_SCALES.iter().find(|scale| **scale > self.time_scale);
+        self.time_scale = *faster.unwrap_or(&TIME_SCALES[TIME_SCALES.len() - 1]);
+    }
 
-            // println!("Busy: {}ms\tSleeping: {}ms", busy_duration.as_millis(), sleep_duration.as_millis());            
-            
-            // onset of next tick
-            self.scheduled_tick += self.tick_interval;  
-            while self.scheduled_tick < std::time::Instant::now() {
-                self.scheduled_tick += self.tick_interval;
-                // println!("Rescheduled tick");
+    /// Game time to simulate this tick: the real time since the last tick times the time
+    /// scale, zero while paused unless a step was requested.
+    pub fn game_delta(&mut self) -> std::time::Duration {
+        if self.paused {
+            if std::mem::take(&mut self.step_requested) {
+                return STEP_DURATION;
             }
+            return std::time::Duration::ZERO;
+        }
+        self.elapsed_since_last_tick().mul_f32(self.time_scale)
+    }
 
-            self.previous_tick_actual = self.current_tick_actual;
-            self.current_tick_actual = std::time::Instant::now();
+    pub fn from_target_fps(target_fps: u32) -> Self {
+        let current_tick = std::time::Instant::now();
+        let next_tick = current_tick;
+        let previous_tick = current_tick;
+        let tick_interval = std::time::Duration::from_secs_f32(1.0 / target_fps as f32);
+        let frame_count = 0;
+        let last_fps_check = std::time::Instant::now();
 
+        TickController {
+            target_fps,
+            frame_count,
+            current_tick_actual: current_tick,
+            scheduled_tick: next_tick,
+            previous_tick_actual: previous_tick,
+            tick_interval,
+            last_fps_check,
+            paused: false,
+            step_requested: false,
+            time_scale: 1.0,
+            last_fps: 0.0,
+            skipped_frames: 0,
+            busy_duration: std::time::Duration::ZERO,
         }
+    }
 
-        pub fn elapsed_since_last_tick(&self) -> std::time::Duration {
-            self.previous_tick_actual.elapsed()
+    pub fn wait_for_next_tick(&mut self) {
+        self.frame_count += 1;
+        if self.last_fps_check.elapsed().as_secs_f32() > 1.0 {
+            self.fps_check();
         }
 
-        // pub fn time_to_next_tick(&self) -> std::time::Duration {
-        //     self.scheduled_tick.saturating_duration_since(std::time::Instant::now())
-        // }
+        let now: std::time::Instant = std::time::Instant::now();
+        let sleep_duration = self.scheduled_tick.saturating_duration_since(now);
+        let busy_duration = now.saturating_duration_since(self.current_tick_actual);
+        self.busy_duration = busy_duration;
 
-        // pub fn delta_t(&self) -> std::time::Duration {
-        //     self.tick_interval
-        // }
+        if sleep_duration.as_micros() > 0 {
+            while std::time::Instant::now() < self.scheduled_tick {
+                std::hint::spin_loop();
+            }
+            //std::thread::sleep(sleep_duration);
+        }
+
+        // println!("Busy: {}ms\tSleeping: {}ms", busy_duration.as_millis(), sleep_duration.as_millis());            
+        
+        // onset of next tick
+        self.scheduled_tick += self.tick_interval;  
+        while self.scheduled_tick < std::time::Instant::now() {
+            self.scheduled_tick += self.tick_interval;
+            self.skipped_frames += 1;
+            // println!("Rescheduled tick");
+        }
+
+        self.previous_tick_actual = self.current_tick_actual;
+        self.current_tick_actual = std::time::Instant::now();
+
+    }
+
+    pub fn elapsed_since_last_tick(&self) -> std::time::Duration {
+        self.previous_tick_actual.elapsed()
+    }
+
+    // pub fn time_to_next_tick(&self) -> std::time::Duration {
+    //     self.scheduled_tick.saturating_duration_since(std::time::Instant::now())
+    // }
+
+    // pub fn delta_t(&self) -> std::time::Duration {
+    //     self.tick_interval
+    // }
+
+    pub fn fps_check(&mut self) -> f32 {
+        let elapsed = self.last_fps_check.elapsed().as_secs_f32();
+        let fps = self.frame_count as f32 / elapsed;
+        self.frame_count = 0;
+        self.last_fps_check = std::time::Instant::now();
+        self.last_fps = fps;
+        fps
+    }
 
-        pub fn fps_check(&mut self) -> f32 {
-            let elapsed = self.last_fps_check.elapsed().as_secs_f32();
-            let fps = self.frame_count as f32 / elapsed;
-            self.frame_count = 0;
-            self.last_fps_check = std::time::Instant::now();
-            return fps;
+    pub fn stats(&self) -> FrameStats {
+        FrameStats {
+            target_fps: self.target_fps,
+            fps: self.last_fps,
+            skipped_frames: self.skipped_frames,
+            busy_duration: self.busy_duration,
+            tick_interval: self.tick_interval,
+            paused: self.paused,
+            time_scale: self.time_scale,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tick_controller::tick_controller::TickController;
+    use crate::tick_controller::{TickController, STEP_DURATION};
 
     #[test]
     fn fps_test() {
@@ -119,7 +204,46 @@ mod tests {
             frame_count += 1;
         }
         println!("Frames: {}", frame_count);
-        assert_eq!(frame_count >= target_fps - 1 && frame_count <= target_fps + 1, true);
+        assert!(frame_count >= target_fps - 1 && frame_count <= target_fps + 1);
+    }
+
+    #[test]
+    fn pause_and_step_test() {
+        let mut tick_controller = TickController::from_target_fps(60);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert!(tick_controller.game_delta() > std::time::Duration::ZERO);
+
+        // stepping only works while paused and only lasts one tick
+        tick_controller.step();
+        tick_controller.pause();
+        assert_eq!(tick_controller.game_delta(), std::time::Duration::ZERO);
+        tick_controller.step();
+        assert_eq!(tick_controller.game_delta(), STEP_DURATION);
+        assert_eq!(tick_controller.game_delta(), std::time::Duration::ZERO);
+
+        tick_controller.toggle_pause();
+        assert!(!tick_controller.stats().paused);
+    }
+
+    #[test]
+    fn time_scale_test() {
+        let mut tick_controller = TickController::from_target_fps(60);
+        tick_controller.slower();
+        assert_eq!(tick_controller.time_scale(), 0.5);
+        tick_controller.faster();
+        tick_controller.faster();
+        assert_eq!(tick_controller.time_scale(), 1.5);
+        (0..10).for_each(|_| tick_controller.slower());
+        assert_eq!(tick_controller.time_scale(), 0.1);
+
+        // the delta is measured before and after, the scaled one has to fall in between
+        tick_controller.set_time_scale(0.5);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let before = tick_controller.elapsed_since_last_tick();
+        let scaled = tick_controller.game_delta();
+        let after = tick_controller.elapsed_since_last_tick();
+        let tolerance = std::time::Duration::from_micros(1);
+        assert!(scaled + tolerance >= before / 2 && scaled <= after / 2 + tolerance);
     }
 
     #[test]
@@ -148,9 +272,4 @@ mod tests {
         println!("Elapsed: {}s", elapsed.as_secs_f32());       
         println!("Count: {}", count); 
     }
-}
-
-struct FrameStats {
-    frame_count: u64,
-    skipped_frames: u64,
 }
\ No newline at end of file
@@ -0,0 +1,443 @@
+#![allow(dead_code)]
+
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::{FPoint, FRect};
+use sdl2::render::{BlendMode, Canvas, RenderTarget};
+
+use crate::draw_primitives::draw_primitives::{draw_rounded_rect, draw_text, fill_rounded_rect, text_size, TextAlign};
+
+// navigation gathered from events between two frames
+#[derive(Copy, Clone, Default, Debug)]
+struct UiInput {
+    up: bool,
+    down: bool,
+    left: bool,
+    right: bool,
+    activate: bool,
+    back: bool,
+    // latest mouse position, only set when the mouse moved or clicked
+    mouse: Option<FPoint>,
+    mouse_pressed: bool,
+    mouse_released: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum WidgetKind {
+    Label,
+    Button,
+    Slider { fraction: f32 },
+    Toggle { on: bool },
+    List,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Widget {
+    kind: WidgetKind,
+    rect: FRect,
+    label: String,
+    value: String,
+    focused: bool,
+}
+
+/// Immediate mode widgets: every frame the screen calls `begin`, declares its widgets,
+/// reacts to their return values and calls `end`. What was declared is drawn by `draw`.
+/// Widgets are stacked vertically below `position`, focus moves with the arrow keys,
+/// the d-pad or the mouse.
+pub struct Ui {
+    pub position: FPoint,
+    pub width: f32,
+    pub row_height: f32,
+    pub text_scale: f32,
+    pub color: Color,
+    input: UiInput,
+    mouse: Option<FPoint>,
+    mouse_held: bool,
+    // index among the focusable widgets
+    focus: usize,
+    focusable_count: usize,
+    dragging: Option<usize>,
+    widgets: Vec<Widget>,
+    cursor_y: f32,
+}
+
+impl Ui {
+    pub fn new(position: FPoint, width: f32) -> Ui {
+        Ui {
+            position,
+            width,
+            row_height: 36.0,
+            text_scale: 3.0,
+            color: Color::WHITE,
+            input: UiInput::default(),
+            mouse: None,
+            mouse_held: false,
+            focus: 0,
+            focusable_count: 0,
+            dragging: None,
+            widgets: Vec::new(),
+            cursor_y: position.y(),
+        }
+    }
+
+    pub fn focus(&self) -> usize {
+        self.focus
+    }
+
+    pub fn set_focus(&mut self, focus: usize) {
+        self.focus = focus;
+    }
+
+    /// Collects navigation from keyboard, game controller and mouse events. Mouse positions
+    /// are expected in the coordinates the widgets are laid out in.
+    pub fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::KeyDown { keycode: Some(keycode), .. } => match *keycode {
+                Keycode::Up => self.input.up = true,
+                Keycode::Down => self.input.down = true,
+                Keycode::Left => self.input.left = true,
+                Keycode::Right => self.input.right = true,
+                Keycode::Return | Keycode::Space => self.input.activate = true,
+                Keycode::Escape | Keycode::Backspace => self.input.back = true,
+                _ => {}
+            },
+            Event::ControllerButtonDown { button, .. } => match *button {
+                Button::DPadUp => self.input.up = true,
+                Button::DPadDown => self.input.down = true,
+                Button::DPadLeft => self.input.left = true,
+                Button::DPadRight => self.input.right = true,
+                Button::A | Button::Start => self.input.activate = true,
+                Button::B | Button::Back => self.input.back = true,
+                _ => {}
+            },
+            Event::MouseMotion { x, y, .. } => {
+                self.input.mouse = Some(FPoint::new(*x as f32, *y as f32));
+            }
+            Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => {
+                self.input.mouse = Some(FPoint::new(*x as f32, *y as f32));
+                self.input.mouse_pressed = true;
+                self.mouse_held = true;
+            }
+            Event::MouseButtonUp { mouse_btn: MouseButton::Left, .. } => {
+                self.input.mouse_released = true;
+                self.mouse_held = false;
+            }
+            _ => {}
+        }
+        if let Some(mouse) = self.input.mouse {
+            self.mouse = Some(mouse);
+        }
+    }
+
+    /// Whether back (Escape, B) was pressed this frame.
+    pub fn back(&self) -> bool {
+        self.input.back
+    }
+
+    pub fn begin(&mut self) {
+        self.widgets.clear();
+        self.cursor_y = self.position.y();
+
+        if self.focusable_count > 0 {
+            if self.input.up {
+                self.focus = (self.focus + self.focusable_count - 1) % self.focusable_count;
+            }
+            if self.input.down {
+                self.focus = (self.focus + 1) % self.focusable_count;
+            }
+        }
+        self.focusable_count = 0;
+    }
+
+    pub fn end(&mut self) {
+        if self.focusable_count > 0 {
+            self.focus = self.focus.min(self.focusable_count - 1);
+        }
+        if !self.mouse_held {
+            self.dragging = None;
+        }
+        self.input = UiInput::default();
+    }
+
+    // lays out the next row, returns its rect, its focus index and whether it is focused
+    fn next_row(&mut self, focusable: bool) -> (FRect, usize, bool) {
+        let rect = FRect::new(self.position.x(), self.cursor_y, self.width, self.row_height);
+        self.cursor_y += self.row_height * 1.25;
+        if !focusable {
+            return (rect, usize::MAX, false);
+        }
+
+        let id = self.focusable_count;
+        self.focusable_count += 1;
+        // the mouse only takes the focus when it moved or clicked this frame
+        if let Some(mouse) = self.input.mouse {
+            if rect.contains_point(mouse) {
+                self.focus = id;
+            }
+        }
+        (rect, id, self.focus == id)
+    }
+
+    fn clicked(&self, rect: FRect) -> bool {
+        self.input.mouse_pressed && self.input.mouse.is_some_and(|mouse| rect.contains_point(mouse))
+    }
+
+    fn push(&mut self, kind: WidgetKind, rect: FRect, label: &str, value: String, focused: bool) {
+        self.widgets.push(Widget {
+            kind,
+            rect,
+            label: label.to_string(),
+            value,
+            focused,
+        });
+    }
+
+    /// Heading text, never takes the focus.
+    pub fn label(&mut self, text: &str) {
+        let (rect, _, _) = self.next_row(false);
+        self.push(WidgetKind::Label, rect, text, String::new(), false);
+    }
+
+    /// Returns true on the frame the button is activated.
+    pub fn button(&mut self, label: &str) -> bool {
+        let (rect, _, focused) = self.next_row(true);
+        let pressed = (focused && self.input.activate) || self.clicked(rect);
+        self.push(WidgetKind::Button, rect, label, String::new(), focused);
+        pressed
+    }
+
+    /// Left/right move the value by `step`, the mouse drags it along the row. Returns true
+    /// when the value changed.
+    pub fn slider(&mut self, label: &str, value: &mut f32, min: f32, max: f32, step: f32) -> bool {
+        let (rect, id, focused) = self.next_row(true);
+        let before = *value;
+
+        if focused && self.input.left {
+            *value -= step;
+        }
+        if focused && self.input.right {
+            *value += step;
+        }
+        if self.clicked(rect) {
+            self.dragging = Some(id);
+        }
+        if self.dragging == Some(id) && self.mouse_held {
+            if let Some(mouse) = self.mouse {
+                let fraction = ((mouse.x() - rect.left()) / rect.width()).clamp(0.0, 1.0);
+                let raw = min + fraction * (max - min);
+                *value = if step > 0.0 { min + ((raw - min) / step).round() * step } else { raw };
+            }
+        }
+        *value = value.clamp(min, max);
+
+        let fraction = if max > min { (*value - min) / (max - min) } else { 0.0 };
+        let text = if step < 1.0 { format!("{:.2}", *value) } else { format!("{:.0}", *value) };
+        self.push(WidgetKind::Slider { fraction }, rect, label, text, focused);
+        *value != before
+    }
+
+    /// Activating or left/right flips the value. Returns true when it changed.
+    pub fn toggle(&mut self, label: &str, value: &mut bool) -> bool {
+        let (rect, _, focused) = self.next_row(true);
+        let flipped = (focused && (self.input.activate || self.input.left || self.input.right)) || self.clicked(rect);
+        if flipped {
+            *value = !*value;
+        }
+        self.push(WidgetKind::Toggle { on: *value }, rect, label, String::from(if *value { "ON" } else { "OFF" }), focused);
+        flipped
+    }
+
+    /// Cycles through `items` with left/right, activate or a click. Returns true when the
+    /// selection changed.
+    pub fn list(&mut self, label: &str, items: &[&str], selected: &mut usize) -> bool {
+        let (rect, _, focused) = self.next_row(true);
+        if items.is_empty() {
+            self.push(WidgetKind::List, rect, label, String::new(), focused);
+            return false;
+        }
+
+        let before = *selected;
+        if focused && self.input.left {
+            *selected = (*selected + items.len() - 1) % items.len();
+        }
+        if (focused && (self.input.right || self.input.activate)) || self.clicked(rect) {
+            *selected = (*selected + 1) % items.len();
+        }
+        *selected = (*selected).min(items.len() - 1);
+
+        self.push(WidgetKind::List, rect, label, format!("< {} >", items[*selected]), focused);
+        *selected != before
+    }
+
+    pub fn draw<T: RenderTarget>(&self, canvas: &mut Canvas<T>) -> Result<(), String> {
+        let color = self.color;
+        let faint = Color::RGBA(color.r, color.g, color.b, color.a / 4);
+        let (_, text_height) = text_size("X", self.text_scale);
+        let padding = 10.0;
+
+        let blend_mode = canvas.blend_mode();
+        canvas.set_blend_mode(BlendMode::Blend);
+
+        for widget in &self.widgets {
+            let rect = widget.rect;
+            let text_y = rect.center().y() - text_height / 2.0;
+
+            if widget.focused {
+                canvas.set_draw_color(faint);
+                fill_rounded_rect(canvas, rect, 6.0, true)?;
+                canvas.set_draw_color(color);
+                draw_rounded_rect(canvas, rect, 6.0)?;
+            }
+
+            match widget.kind {
+                WidgetKind::Label => {
+                    draw_text(canvas, &widget.label, FPoint::new(rect.center().x(), text_y), self.text_scale, TextAlign::Center, color)?;
+                    continue;
+                }
+                WidgetKind::Button => {
+                    draw_text(canvas, &widget.label, FPoint::new(rect.center().x(), text_y), self.text_scale, TextAlign::Center, color)?;
+                    continue;
+                }
+                WidgetKind::Slider { fraction } => {
+                    // track along the bottom of the row
+                    let track = FRect::new(rect.left() + padding, rect.bottom() - 6.0, rect.width() - padding * 2.0, 3.0);
+                    canvas.set_draw_color(faint);
+                    canvas.fill_frect(track)?;
+                    canvas.set_draw_color(color);
+                    canvas.fill_frect(FRect::new(track.left(), track.top(), track.width() * fraction, track.height()))?;
+                }
+                WidgetKind::Toggle { on } => {
+                    let size = text_height;
+                    let (value_width, _) = text_size(&widget.value, self.text_scale);
+                    let check = FRect::new(rect.right() - padding - value_width - size * 1.5, text_y, size, size);
+                    canvas.set_draw_color(color);
+                    if on {
+                        canvas.fill_frect(check)?;
+                    } else {
+                        canvas.draw_frect(check)?;
+                    }
+                }
+                WidgetKind::List => {}
+            }
+
+            draw_text(canvas, &widget.label, FPoint::new(rect.left() + padding, text_y), self.text_scale, TextAlign::Left, color)?;
+            draw_text(canvas, &widget.value, FPoint::new(rect.right() - padding, text_y), self.text_scale, TextAlign::Right, color)?;
+        }
+
+        canvas.set_blend_mode(blend_mode);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::key;
+    use crate::ui::Ui;
+    use sdl2::controller::Button;
+    use sdl2::event::Event;
+    use sdl2::keyboard::Keycode;
+    use sdl2::mouse::{MouseButton, MouseState};
+    use sdl2::pixels::{Color, PixelFormatEnum};
+    use sdl2::rect::{FPoint, Rect};
+    use sdl2::surface::Surface;
+
+    struct Options {
+        speed: f32,
+        trail: bool,
+        mode: usize,
+        saved: bool,
+    }
+
+    // one frame of an options screen
+    fn frame(ui: &mut Ui, options: &mut Options) {
+        ui.begin();
+        ui.label("OPTIONS");
+        ui.slider("SPEED", &mut options.speed, 0.0, 100.0, 10.0);
+        ui.toggle("TRAIL", &mut options.trail);
+        ui.list("MODE", &["A", "B", "C"], &mut options.mode);
+        if ui.button("SAVE") {
+            options.saved = true;
+        }
+        ui.end();
+    }
+
+    #[test]
+    fn keyboard_and_gamepad_test() {
+        let mut ui = Ui::new(FPoint::new(0.0, 0.0), 200.0);
+        let mut options = Options { speed: 50.0, trail: false, mode: 0, saved: false };
+        frame(&mut ui, &mut options);
+        // the label is skipped, the slider has the focus
+        assert_eq!(ui.focus(), 0);
+
+        ui.handle_event(&key(Keycode::Right));
+        frame(&mut ui, &mut options);
+        assert_eq!(options.speed, 60.0);
+
+        ui.handle_event(&key(Keycode::Down));
+        ui.handle_event(&key(Keycode::Return));
+        frame(&mut ui, &mut options);
+        assert!(options.trail);
+
+        let dpad = |button| Event::ControllerButtonDown { timestamp: 0, which: 0, button };
+        ui.handle_event(&dpad(Button::DPadDown));
+        ui.handle_event(&dpad(Button::DPadLeft));
+        frame(&mut ui, &mut options);
+        assert_eq!(options.mode, 2);
+
+        // wraps around from the last widget to the first
+        ui.handle_event(&dpad(Button::DPadDown));
+        ui.handle_event(&dpad(Button::A));
+        frame(&mut ui, &mut options);
+        assert!(options.saved);
+        ui.handle_event(&key(Keycode::Down));
+        frame(&mut ui, &mut options);
+        assert_eq!(ui.focus(), 0);
+    }
+
+    #[test]
+    fn mouse_test() {
+        let mut ui = Ui::new(FPoint::new(0.0, 0.0), 200.0);
+        let mut options = Options { speed: 50.0, trail: false, mode: 0, saved: false };
+        frame(&mut ui, &mut options);
+
+        // rows are 36 high with 9 units of spacing, the slider is the second row
+        let press = |x, y| Event::MouseButtonDown { timestamp: 0, window_id: 0, which: 0, mouse_btn: MouseButton::Left, clicks: 1, x, y };
+        let motion = |x, y| Event::MouseMotion { timestamp: 0, window_id: 0, which: 0, mousestate: MouseState::from_sdl_state(1), x, y, xrel: 0, yrel: 0 };
+        ui.handle_event(&press(20, 60));
+        frame(&mut ui, &mut options);
+        assert_eq!(options.speed, 10.0);
+
+        // dragging keeps working outside of the row
+        ui.handle_event(&motion(190, 300));
+        frame(&mut ui, &mut options);
+        assert_eq!(options.speed, 100.0);
+        ui.handle_event(&Event::MouseButtonUp { timestamp: 0, window_id: 0, which: 0, mouse_btn: MouseButton::Left, clicks: 1, x: 190, y: 300 });
+        frame(&mut ui, &mut options);
+
+        ui.handle_event(&press(100, 100));
+        frame(&mut ui, &mut options);
+        assert!(options.trail);
+        assert_eq!(ui.focus(), 1);
+    }
+
+    #[test]
+    fn draw_test() {
+        let surface = Surface::new(200, 240, PixelFormatEnum::RGBA32).unwrap();
+        let mut canvas = surface.into_canvas().unwrap();
+        let mut ui = Ui::new(FPoint::new(0.0, 0.0), 200.0);
+        ui.color = Color::GREEN;
+        let mut options = Options { speed: 50.0, trail: true, mode: 1, saved: false };
+        frame(&mut ui, &mut options);
+        ui.draw(&mut canvas).unwrap();
+
+        // the slider is the second row, its track runs 10 to 190 along y 75 and is filled halfway
+        let pixels = canvas.read_pixels(Rect::new(0, 0, 200, 240), PixelFormatEnum::RGBA32).unwrap();
+        let at = |x: usize, y: usize| Color::RGBA(pixels[(y * 200 + x) * 4], pixels[(y * 200 + x) * 4 + 1], pixels[(y * 200 + x) * 4 + 2], 255);
+        assert_eq!(at(50, 76), Color::GREEN);
+        assert!(at(150, 76).g > 0 && at(150, 76).g < 200);
+        assert_eq!(at(5, 5), Color::BLACK);
+    }
+}